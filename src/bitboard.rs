@@ -0,0 +1,332 @@
+use std::sync::OnceLock;
+
+// a set of squares, one bit per square in board order (a1 == bit 0, h8 == bit 63)
+pub type Bitboard = u64;
+
+pub const RANK_1: Bitboard = 0xFF;
+pub const RANK_2: Bitboard = RANK_1 << 8;
+pub const RANK_7: Bitboard = RANK_1 << 48;
+pub const RANK_8: Bitboard = RANK_1 << 56;
+
+// squares attacked by a piece that moves by fixed steps, for every square it can stand on
+const fn step_attacks(steps: [(i8, i8); 8], count: usize) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let (file, rank) = ((square % 8) as i8, (square / 8) as i8);
+        let mut i = 0;
+        while i < count {
+            let (f, r) = (file + steps[i].0, rank + steps[i].1);
+            if 0 <= f && f < 8 && 0 <= r && r < 8 {
+                table[square] |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+pub static KNIGHT_ATTACKS: [Bitboard; 64] = step_attacks(
+    [
+        (1, 2),
+        (2, 1),
+        (2, -1),
+        (1, -2),
+        (-1, -2),
+        (-2, -1),
+        (-2, 1),
+        (-1, 2),
+    ],
+    8,
+);
+pub static KING_ATTACKS: [Bitboard; 64] = step_attacks(
+    [
+        (0, 1),
+        (1, 1),
+        (1, 0),
+        (1, -1),
+        (0, -1),
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+    ],
+    8,
+);
+// squares a pawn attacks, indexed by colour (white == 0)
+pub static PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    step_attacks(
+        [
+            (-1, 1),
+            (1, 1),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+        ],
+        2,
+    ),
+    step_attacks(
+        [
+            (-1, -1),
+            (1, -1),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+        ],
+        2,
+    ),
+];
+
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+pub fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    let tables = sliding_tables();
+    tables.attacks[tables.bishop[square].index(occupied)]
+}
+
+pub fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    let tables = sliding_tables();
+    tables.attacks[tables.rook[square].index(occupied)]
+}
+
+pub fn queen_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupied) | rook_attacks(square, occupied)
+}
+
+// iterate over the squares in a bitboard, lowest first
+pub fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+/* ------- M A G I C S ---------*/
+
+// multiplying the blockers on a slider's rays by a magic number gathers them into the top bits,
+// which index a table of attacks for that exact set of blockers
+struct Magic {
+    // the squares whose occupancy matters: the rays without the edge squares they end on
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    // start of this square's attacks in the shared table
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct SlidingTables {
+    bishop: Vec<Magic>,
+    rook: Vec<Magic>,
+    attacks: Vec<Bitboard>,
+}
+
+// magics are searched for the first time sliding attacks are needed, seeded so the search
+// always finds the same numbers
+fn sliding_tables() -> &'static SlidingTables {
+    static TABLES: OnceLock<SlidingTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let mut attacks = vec![];
+        let bishop = (0..64)
+            .map(|square| find_magic(square, &BISHOP_DIRECTIONS, &mut rng, &mut attacks))
+            .collect();
+        let rook = (0..64)
+            .map(|square| find_magic(square, &ROOK_DIRECTIONS, &mut rng, &mut attacks))
+            .collect();
+        SlidingTables {
+            bishop,
+            rook,
+            attacks,
+        }
+    })
+}
+
+// walk each direction from square until the edge or the first blocker (included)
+fn ray_attacks(square: usize, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = 0;
+    for (d_file, d_rank) in directions {
+        let (mut file, mut rank) = ((square % 8) as i8, (square / 8) as i8);
+        loop {
+            file += d_file;
+            rank += d_rank;
+            if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+                break;
+            }
+            let bit = 1 << (rank * 8 + file);
+            attacks |= bit;
+            if occupied & bit != 0 {
+                break;
+            }
+        }
+    }
+    attacks
+}
+
+fn relevant_mask(square: usize, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = 0;
+    for (d_file, d_rank) in directions {
+        let (mut file, mut rank) = ((square % 8) as i8, (square / 8) as i8);
+        // a square only matters if the ray carries on past it
+        while (0..8).contains(&(file + 2 * d_file)) && (0..8).contains(&(rank + 2 * d_rank)) {
+            file += d_file;
+            rank += d_rank;
+            mask |= 1 << (rank * 8 + file);
+        }
+    }
+    mask
+}
+
+fn find_magic(
+    square: usize,
+    directions: &[(i8, i8)],
+    rng: &mut Rng,
+    attacks: &mut Vec<Bitboard>,
+) -> Magic {
+    let mask = relevant_mask(square, directions);
+    let bits = mask.count_ones();
+    // every subset of the mask, with the attacks it allows
+    let mut occupancies = vec![];
+    let mut subset: Bitboard = 0;
+    loop {
+        occupancies.push((subset, ray_attacks(square, subset, directions)));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let mut table = vec![0; 1 << bits];
+    // the attempt each table entry was last written in, saves clearing the table every time
+    let mut written = vec![0u32; 1 << bits];
+    let mut attempt = 0;
+    loop {
+        let magic = rng.sparse();
+        // good magics spread the mask's bits into the top byte
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+        let fits = occupancies.iter().all(|(occupied, attack)| {
+            let i = (occupied.wrapping_mul(magic) >> (64 - bits)) as usize;
+            if written[i] != attempt {
+                written[i] = attempt;
+                table[i] = *attack;
+                true
+            } else {
+                // two blocker sets may share an entry only if they allow the same attacks
+                table[i] == *attack
+            }
+        });
+        if fits {
+            let offset = attacks.len();
+            attacks.extend(table);
+            return Magic {
+                mask,
+                magic,
+                shift: 64 - bits,
+                offset,
+            };
+        }
+    }
+}
+
+// xorshift64
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    // few bits set, which makes a good magic more likely
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> usize {
+        let bytes = name.as_bytes();
+        (bytes[1] - b'1') as usize * 8 + (bytes[0] - b'a') as usize
+    }
+
+    fn bitboard(names: &[&str]) -> Bitboard {
+        names.iter().fold(0, |bb, name| bb | 1 << square(name))
+    }
+
+    #[test]
+    fn step_tables() {
+        assert_eq!(KNIGHT_ATTACKS[square("a1")], bitboard(&["b3", "c2"]));
+        assert_eq!(KNIGHT_ATTACKS[square("e4")].count_ones(), 8);
+        assert_eq!(KING_ATTACKS[square("h8")], bitboard(&["g8", "g7", "h7"]));
+        assert_eq!(PAWN_ATTACKS[0][square("a2")], bitboard(&["b3"]));
+        assert_eq!(PAWN_ATTACKS[1][square("e7")], bitboard(&["d6", "f6"]));
+        assert_eq!(PAWN_ATTACKS[0][square("e8")], 0);
+    }
+
+    #[test]
+    fn sliding_attacks() {
+        let occupied = bitboard(&["d6", "b4", "f3", "g4"]);
+        assert_eq!(
+            rook_attacks(square("d4"), occupied),
+            bitboard(&["d5", "d6", "c4", "b4", "e4", "f4", "g4", "d3", "d2", "d1"])
+        );
+        assert_eq!(
+            bishop_attacks(square("d4"), occupied),
+            bitboard(&[
+                "e5", "f6", "g7", "h8", "c5", "b6", "a7", "c3", "b2", "a1", "e3", "f2", "g1"
+            ])
+        );
+        assert_eq!(queen_attacks(square("a1"), 0).count_ones(), 21);
+    }
+
+    #[test]
+    fn magics_match_ray_walking() {
+        // pseudo random blockers on every square
+        let mut rng = Rng(7);
+        for square in 0..64 {
+            for _ in 0..50 {
+                let occupied = rng.next() & rng.next();
+                assert_eq!(
+                    bishop_attacks(square, occupied),
+                    ray_attacks(square, occupied, &BISHOP_DIRECTIONS)
+                );
+                assert_eq!(
+                    rook_attacks(square, occupied),
+                    ray_attacks(square, occupied, &ROOK_DIRECTIONS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_squares() {
+        assert_eq!(
+            squares(bitboard(&["h8", "a1", "e4"])).collect::<Vec<usize>>(),
+            vec![0, 28, 63]
+        );
+        assert_eq!(squares(0).count(), 0);
+    }
+}
@@ -4,21 +4,26 @@ use std::{
 };
 
 use crate::{
+    bitboard::{
+        self, bishop_attacks, queen_attacks, rook_attacks, Bitboard, KING_ATTACKS, KNIGHT_ATTACKS,
+        PAWN_ATTACKS, RANK_1, RANK_2, RANK_7, RANK_8,
+    },
     coordinate::Coordinate,
     errors::BoardError,
-    move_logic::{self, in_check, Move, MoveType},
-    pieces::{Colour, Piece},
+    move_logic::{self, Move, MoveType},
+    pieces::{Colour, Piece, PieceType},
+    zobrist,
 };
 
 // struct to represent castling rights
-#[derive(Debug, PartialEq)]
-struct CastlingRights {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CastlingRights {
     //white side
-    k_w: bool,
-    q_w: bool,
+    pub k_w: bool,
+    pub q_w: bool,
     //black side
-    k_b: bool,
-    q_b: bool,
+    pub k_b: bool,
+    pub q_b: bool,
 }
 impl Display for CastlingRights {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -34,45 +39,149 @@ impl Display for CastlingRights {
         if self.q_b {
             write!(f, "q")?;
         }
+        if !(self.k_w || self.q_w || self.k_b || self.q_b) {
+            write!(f, "-")?;
+        }
         write!(f, "")
     }
 }
 impl FromStr for CastlingRights {
     type Err = BoardError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(CastlingRights {
+                k_w: false,
+                q_w: false,
+                k_b: false,
+                q_b: false,
+            });
+        }
         let len = s.chars().count();
         if len > 4 {
             return Err(BoardError::CastlingRightsError);
         }
         let (mut k_w, mut q_w, mut k_b, mut q_b) = (false, false, false, false);
         for c in s.chars() {
-            match c {
-                'K' => k_w = true,
-                'Q' => q_w = true,
-                'k' => k_b = true,
-                'q' => q_b = true,
+            let right = match c {
+                'K' => &mut k_w,
+                'Q' => &mut q_w,
+                'k' => &mut k_b,
+                'q' => &mut q_b,
                 _ => return Err(BoardError::CastlingRightsError),
+            };
+            // each right may only appear once
+            if *right {
+                return Err(BoardError::CastlingRightsError);
             }
+            *right = true;
         }
 
         Ok(CastlingRights { k_w, q_w, k_b, q_b })
     }
 }
-#[derive(Debug)]
-#[allow(unused)]
-enum GameState {
+impl CastlingRights {
+    // remove any rights lost by moving a piece from i0 to i
+    // a king move loses both rights, a rook moving from (or being captured on) its corner loses one
+    fn update(&mut self, i0: usize, i: usize, moved_piece_type: PieceType) {
+        if moved_piece_type == PieceType::King {
+            match i0 {
+                4 => (self.k_w, self.q_w) = (false, false),
+                60 => (self.k_b, self.q_b) = (false, false),
+                _ => {}
+            }
+        }
+        for corner in [i0, i] {
+            match corner {
+                0 => self.q_w = false,
+                7 => self.k_w = false,
+                56 => self.q_b = false,
+                63 => self.k_b = false,
+                _ => {}
+            }
+        }
+    }
+}
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameState {
     Active,
+    // by checkmate
     BlackWin,
     WhiteWin,
-    Forfeit,
     Stalemate,
-    Resignation,
+    // the side that resigned
+    Resignation(Colour),
+    // the side whose clock ran out
+    OutOfTime(Colour),
+    Draw(DrawReason),
+}
+impl Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            GameState::Active => "game in progress",
+            GameState::BlackWin => "checkmate! black wins 0-1",
+            GameState::WhiteWin => "checkmate! white wins 1-0",
+            GameState::Stalemate => "stalemate! draw 1/2-1/2",
+            GameState::Resignation(Colour::White) => "white resigns! black wins 0-1",
+            GameState::Resignation(Colour::Black) => "black resigns! white wins 1-0",
+            GameState::OutOfTime(Colour::White) => "white is out of time! black wins 0-1",
+            GameState::OutOfTime(Colour::Black) => "black is out of time! white wins 1-0",
+            GameState::Draw(reason) => return write!(f, "draw by {}! 1/2-1/2", reason),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl GameState {
+    // the pgn result token, "*" when the game is still going or the winner isn't known
+    pub fn result(&self) -> &'static str {
+        match self {
+            GameState::WhiteWin
+            | GameState::Resignation(Colour::Black)
+            | GameState::OutOfTime(Colour::Black) => "1-0",
+            GameState::BlackWin
+            | GameState::Resignation(Colour::White)
+            | GameState::OutOfTime(Colour::White) => "0-1",
+            GameState::Stalemate | GameState::Draw(_) => "1/2-1/2",
+            _ => "*",
+        }
+    }
+}
+
+// threefold repetition and the fifty move rule must be claimed by a player, with the draw command
+// in play (see Board::claim_draw), the others end the game automatically
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DrawReason {
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    Agreement,
+}
+impl Display for DrawReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::FivefoldRepetition => "fivefold repetition",
+            DrawReason::FiftyMoveRule => "the fifty move rule",
+            DrawReason::SeventyFiveMoveRule => "the seventy-five move rule",
+            DrawReason::InsufficientMaterial => "insufficient material",
+            DrawReason::Agreement => "agreement",
+        };
+        write!(f, "{}", s)
+    }
 }
 
 // contains board representation and all relevant information. Constructed from FEN string.
+#[derive(Clone)]
 pub struct Board {
     // where a1 == 0, a2 == 1, h8 == 63
     squares: Vec<Option<Piece>>,
+    // the same pieces as bitboards, indexed by PieceType and Colour, kept in step by set_square()
+    pieces: [Bitboard; 6],
+    colours: [Bitboard; 2],
+    // zobrist key of the position, see hash()
+    hash: u64,
     active_colour: Colour,
     castling_rights: CastlingRights,
     en_passant_target_square: Option<Coordinate>,
@@ -80,71 +189,663 @@ pub struct Board {
     half_move_clock: usize,
     // starts at 1 and increments after black's move
     full_move_number: usize,
+    // updated after every move, no moves are accepted once the game is over
+    game_state: GameState,
+    // hash() of every position reached so far, for repetition detection
+    position_history: Vec<u64>,
+    // each move played with what it changed, most recent last
+    history: Vec<(Move, Undo)>,
+    // moves taken back with undo(), most recently undone last
+    redo_stack: Vec<Move>,
+}
+
+// what make_move() changed that can't be worked out from the move itself, for unmake_move()
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    from: u8,
+    to: u8,
+    move_type: MoveType,
+    // the piece that moved, a pawn for promotions
+    moved: Piece,
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant_target_square: Option<Coordinate>,
+    half_move_clock: usize,
+    hash: u64,
+}
+
+// what make_null_move() changed
+#[derive(Debug, Clone, Copy)]
+pub struct NullUndo {
+    en_passant_target_square: Option<Coordinate>,
+    hash: u64,
 }
 
 impl Board {
     pub fn new(fen: String) -> Result<Board, BoardError> {
         Self::from_str(&fen)
     }
+    // accepts any syntactically valid fen, e.g. positions without kings for analysis
+    // the half-move clock and full-move number may be left out
+    pub fn new_lenient(fen: String) -> Result<Board, BoardError> {
+        Self::parse_fen(&fen, false)
+    }
     pub fn startpos() -> Board {
         Self::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string()).unwrap()
     }
+    pub fn status(&self) -> GameState {
+        self.game_state
+    }
+    // is the side to move in check?
+    pub fn in_check(&self) -> bool {
+        self.king_attacked(self.active_colour)
+    }
+    pub fn piece_at(&self, coord: Coordinate) -> Option<Piece> {
+        let i: usize = coord.into();
+        self.squares[i]
+    }
+    pub fn active_colour(&self) -> Colour {
+        self.active_colour
+    }
+    pub fn full_move_number(&self) -> usize {
+        self.full_move_number
+    }
+    // identifies the position: piece placement, side to move, castling rights and the
+    // en passant file when a capture there is possible. Updated with every move
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+    // hash() of every position since the game started, the current one last
+    pub fn position_hashes(&self) -> &[u64] {
+        &self.position_history
+    }
+    pub fn half_move_clock(&self) -> usize {
+        self.half_move_clock
+    }
+    // the squares holding pieces of one type and colour
+    pub fn pieces(&self, colour: Colour, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type as usize] & self.colours[colour as usize]
+    }
+    pub fn occupied_by(&self, colour: Colour) -> Bitboard {
+        self.colours[colour as usize]
+    }
+    pub fn occupied(&self) -> Bitboard {
+        self.colours[0] | self.colours[1]
+    }
+    // pieces of either colour that attack square i, with sliders blocked only by `occupied`
+    // so pieces lined up behind each other can be found by taking the front one away
+    pub fn attackers_to(&self, i: usize, occupied: Bitboard) -> Bitboard {
+        let [pawns, bishops, knights, rooks, queens, kings] = self.pieces;
+        let white_pawns = PAWN_ATTACKS[Colour::Black as usize][i] & pawns & self.colours[0];
+        let black_pawns = PAWN_ATTACKS[Colour::White as usize][i] & pawns & self.colours[1];
+        white_pawns
+            | black_pawns
+            | KNIGHT_ATTACKS[i] & knights
+            | KING_ATTACKS[i] & kings
+            | bishop_attacks(i, occupied) & (bishops | queens)
+            | rook_attacks(i, occupied) & (rooks | queens)
+    }
     pub fn process_move(&mut self, mv: &Move) -> Result<(), BoardError> {
+        self.play_move(mv)?;
+        // a new move replaces any moves that were taken back
+        self.redo_stack.clear();
+        Ok(())
+    }
+    // take back the last move, restoring the exact position before it
+    // a game a player ended by resigning, running out of time or agreeing a draw stays over
+    pub fn undo(&mut self) -> Result<Move, BoardError> {
+        if matches!(
+            self.game_state,
+            GameState::Resignation(_)
+                | GameState::OutOfTime(_)
+                | GameState::Draw(DrawReason::Agreement)
+        ) {
+            return Err(BoardError::GameOver);
+        }
+        let (mv, undo) = self.history.pop().ok_or(BoardError::NothingToUndo)?;
+        self.unmake_move(undo);
+        // moves are only played while the game is active
+        self.game_state = GameState::Active;
+        self.position_history.pop();
+        self.redo_stack.push(mv);
+        Ok(mv)
+    }
+    // play the last move taken back by undo() again
+    pub fn redo(&mut self) -> Result<Move, BoardError> {
+        let mv = self.redo_stack.pop().ok_or(BoardError::NothingToRedo)?;
+        self.play_move(&mv)?;
+        Ok(mv)
+    }
+    fn play_move(&mut self, mv: &Move) -> Result<(), BoardError> {
+        if self.game_state != GameState::Active {
+            return Err(BoardError::GameOver);
+        }
         let i0: usize = mv.from.into();
         let i: usize = mv.destination.into();
         // causes error if no legal moves exist
-        let move_type = Self::validate_move(self, i0, i)?;
-
-        // get Piece to move
-        let piece = match move_type {
-            // if promotion
-            MoveType::PromotionPush | MoveType::PromotionCapture => match mv.promotion {
-                Some(piece_type) => Piece {
-                    piece_type,
-                    colour: self.active_colour,
-                },
-                None => return Err(BoardError::PromotionError),
-            },
-            // if anything else
-            _ => self.squares[i0].unwrap(),
-        };
-
-        // clone squares and apply changes
-        // check copy for possible attacks on king
-        let mut squares_copy = self.squares.clone();
-        squares_copy[i] = Some(piece);
-        squares_copy[i0] = None;
+        let move_type = self.validate_move(i0, i)?;
 
-        if in_check(&self.active_colour, &squares_copy) {
-            return Err(BoardError::InCheck);
+        if matches!(
+            move_type,
+            MoveType::PromotionPush | MoveType::PromotionCapture
+        ) && mv.promotion.is_none()
+        {
+            return Err(BoardError::PromotionError);
         }
 
-        // move OK, apply changes to board
+        // move OK, keep changes
+        let undo = self.make_move(mv, move_type);
+        self.history.push((*mv, undo));
+        self.position_history.push(self.hash());
+        self.update_game_state();
+        Ok(())
+    }
+    // checkmate or stalemate if the side to move has no legal moves
+    // otherwise check for draws that end the game without a claim
+    fn update_game_state(&mut self) {
+        if !self.has_legal_moves() {
+            if self.king_attacked(self.active_colour) {
+                self.game_state = match self.active_colour {
+                    Colour::White => GameState::BlackWin,
+                    Colour::Black => GameState::WhiteWin,
+                };
+            } else {
+                self.game_state = GameState::Stalemate;
+            }
+        } else if self.repetitions() >= 5 {
+            self.game_state = GameState::Draw(DrawReason::FivefoldRepetition);
+        } else if self.half_move_clock >= 150 {
+            self.game_state = GameState::Draw(DrawReason::SeventyFiveMoveRule);
+        } else if self.insufficient_material() {
+            self.game_state = GameState::Draw(DrawReason::InsufficientMaterial);
+        } else {
+            self.game_state = GameState::Active;
+        }
+    }
+    // a draw the player to move is entitled to claim in the current position
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.game_state != GameState::Active {
+            None
+        } else if self.repetitions() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.half_move_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+    pub fn claim_draw(&mut self) -> Result<DrawReason, BoardError> {
+        if self.game_state != GameState::Active {
+            return Err(BoardError::GameOver);
+        }
+        match self.claimable_draw() {
+            Some(reason) => {
+                self.game_state = GameState::Draw(reason);
+                Ok(reason)
+            }
+            None => Err(BoardError::NoDrawToClaim),
+        }
+    }
+    // the side to move gives up
+    pub fn resign(&mut self) -> Result<(), BoardError> {
+        if self.game_state != GameState::Active {
+            return Err(BoardError::GameOver);
+        }
+        self.game_state = GameState::Resignation(self.active_colour);
+        Ok(())
+    }
+    // the side to move has used up their time
+    pub fn time_out(&mut self) -> Result<(), BoardError> {
+        if self.game_state != GameState::Active {
+            return Err(BoardError::GameOver);
+        }
+        self.game_state = GameState::OutOfTime(self.active_colour);
+        Ok(())
+    }
+    // both players agree to a draw, possible in any position
+    pub fn agree_draw(&mut self) -> Result<(), BoardError> {
+        if self.game_state != GameState::Active {
+            return Err(BoardError::GameOver);
+        }
+        self.game_state = GameState::Draw(DrawReason::Agreement);
+        Ok(())
+    }
+    // number of times the current position has occurred, including now
+    fn repetitions(&self) -> usize {
+        match self.position_history.last() {
+            Some(current) => self
+                .position_history
+                .iter()
+                .filter(|key| *key == current)
+                .count(),
+            None => 0,
+        }
+    }
+    // zobrist key of the en passant file, the target only counts if a pawn is in place to
+    // make the capture so positions that differ in nothing else repeat
+    fn en_passant_hash(&self) -> u64 {
+        match self.en_passant_target_square {
+            Some(coord) if self.can_capture_en_passant(coord.into()) => {
+                zobrist::en_passant(coord.file as usize - 'a' as usize)
+            }
+            _ => 0,
+        }
+    }
+    // the key worked out from scratch, make_move() keeps it up to date from there
+    fn zobrist_hash(&self) -> u64 {
+        let mut hash = self
+            .squares
+            .iter()
+            .enumerate()
+            .filter_map(|(i, square)| square.map(|piece| zobrist::piece(piece, i)))
+            .fold(0, |hash, key| hash ^ key);
+        if self.active_colour == Colour::Black {
+            hash ^= zobrist::black_to_move();
+        }
+        hash ^ zobrist::castling(self.castling_rights) ^ self.en_passant_hash()
+    }
+    fn can_capture_en_passant(&self, target: usize) -> bool {
+        // squares an enemy pawn would capture from are the ones it attacks from the target
+        let pawns =
+            self.pieces[PieceType::Pawn as usize] & self.colours[self.active_colour as usize];
+        PAWN_ATTACKS[self.active_colour.opposite() as usize][target] & pawns != 0
+    }
+    // dead positions: K v K, K+B v K, K+N v K, or only bishops on squares of one colour
+    fn insufficient_material(&self) -> bool {
+        let mut minor_pieces: Vec<(usize, PieceType)> = vec![];
+        for (i, square) in self.squares.iter().enumerate() {
+            match square {
+                Some(p) if p.piece_type == PieceType::King => {}
+                Some(p) if matches!(p.piece_type, PieceType::Bishop | PieceType::Knight) => {
+                    minor_pieces.push((i, p.piece_type))
+                }
+                Some(_) => return false,
+                None => {}
+            }
+        }
+        if minor_pieces.len() <= 1 {
+            return true;
+        }
+        // any number of bishops, all on the same square colour
+        let square_colour = |i: usize| (i / 8 + i % 8) % 2;
+        minor_pieces.iter().all(|(i, t)| {
+            *t == PieceType::Bishop && square_colour(*i) == square_colour(minor_pieces[0].0)
+        })
+    }
+    // like !legal_moves().is_empty() but stops at the first legal move found
+    fn has_legal_moves(&mut self) -> bool {
+        let colour = self.active_colour;
+        self.pseudo_legal_moves().iter().any(|(mv, move_type)| {
+            let undo = self.make_move(mv, *move_type);
+            let legal = !self.king_attacked(colour);
+            self.unmake_move(undo);
+            legal
+        })
+    }
+    // play a pseudo legal move in place, without legality checks or game state and history updates
+    // the returned token takes the move back with unmake_move()
+    pub fn make_move(&mut self, mv: &Move, move_type: MoveType) -> Undo {
+        let i0: usize = mv.from.into();
+        let i: usize = mv.destination.into();
+        let moved = self.squares[i0].unwrap();
+        let captured = match move_type {
+            MoveType::EnPassant(captured) => self.squares[captured],
+            _ => self.squares[i],
+        };
+        let undo = Undo {
+            from: i0 as u8,
+            to: i as u8,
+            move_type,
+            moved,
+            captured,
+            castling_rights: self.castling_rights,
+            en_passant_target_square: self.en_passant_target_square,
+            half_move_clock: self.half_move_clock,
+            hash: self.hash,
+        };
+        // set_square() updates the hash for pieces, everything else is done here
+        self.hash ^= self.en_passant_hash() ^ zobrist::castling(self.castling_rights);
 
-        self.squares[i] = Some(piece);
-        self.squares[i0] = None;
+        let piece = match (move_type, mv.promotion) {
+            (MoveType::PromotionPush | MoveType::PromotionCapture, Some(piece_type)) => Piece {
+                piece_type,
+                colour: self.active_colour,
+            },
+            _ => moved,
+        };
+        self.set_square(i0, None);
+        self.set_square(i, Some(piece));
 
+        // any move other than a double push clears the en passant target
+        self.en_passant_target_square = None;
         match move_type {
-            // add en passant sq
+            // add en passant sq, always on the board
             MoveType::DoublePush(target) => {
-                self.en_passant_target_square = Some(target.try_into()?)
-            }
-            // remove en passant sq
-            MoveType::EnPassant(captured) => {
-                self.squares[captured] = None;
-                self.en_passant_target_square = None;
+                self.en_passant_target_square = Coordinate::try_from(target).ok()
             }
+            // remove captured pawn
+            MoveType::EnPassant(captured) => self.set_square(captured, None),
+            // king has already moved two squares, bring the rook across
+            MoveType::CastleKingSide => self.move_square(i0 + 3, i0 + 1),
+            MoveType::CastleQueenSide => self.move_square(i0 - 4, i0 - 1),
             _ => {}
         }
-        // change active colour
-        self.active_colour.change_colour();
         // change castling rights
+        self.castling_rights.update(i0, i, moved.piece_type);
         // change half-move clock
+        if moved.piece_type == PieceType::Pawn || captured.is_some() {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock += 1;
+        }
         // change full-move number
-        Ok(())
+        if self.active_colour == Colour::Black {
+            self.full_move_number += 1;
+        }
+        // change active colour
+        self.active_colour.change_colour();
+        self.hash ^= zobrist::black_to_move()
+            ^ zobrist::castling(self.castling_rights)
+            ^ self.en_passant_hash();
+        undo
+    }
+    // take back a move played with make_move(), must be the last move made
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.active_colour.change_colour();
+        if self.active_colour == Colour::Black {
+            self.full_move_number -= 1;
+        }
+        let (i0, i) = (undo.from as usize, undo.to as usize);
+        self.set_square(i0, Some(undo.moved));
+        self.set_square(i, undo.captured);
+        match undo.move_type {
+            MoveType::EnPassant(captured) => {
+                self.set_square(i, None);
+                self.set_square(captured, undo.captured);
+            }
+            MoveType::CastleKingSide => self.move_square(i0 + 1, i0 + 3),
+            MoveType::CastleQueenSide => self.move_square(i0 - 1, i0 - 4),
+            _ => {}
+        }
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_target_square = undo.en_passant_target_square;
+        self.half_move_clock = undo.half_move_clock;
+        self.hash = undo.hash;
+    }
+    // pass the move to the opponent, for the engine's null move pruning
+    pub fn make_null_move(&mut self) -> NullUndo {
+        let undo = NullUndo {
+            en_passant_target_square: self.en_passant_target_square,
+            hash: self.hash,
+        };
+        self.hash ^= self.en_passant_hash() ^ zobrist::black_to_move();
+        self.en_passant_target_square = None;
+        self.active_colour.change_colour();
+        undo
+    }
+    pub fn unmake_null_move(&mut self, undo: NullUndo) {
+        self.active_colour.change_colour();
+        self.en_passant_target_square = undo.en_passant_target_square;
+        self.hash = undo.hash;
+    }
+    // every change to squares goes through here to keep the bitboards in step
+    fn set_square(&mut self, i: usize, square: Option<Piece>) {
+        let bit: Bitboard = 1 << i;
+        if let Some(old) = self.squares[i] {
+            self.pieces[old.piece_type as usize] &= !bit;
+            self.colours[old.colour as usize] &= !bit;
+            self.hash ^= zobrist::piece(old, i);
+        }
+        if let Some(new) = square {
+            self.pieces[new.piece_type as usize] |= bit;
+            self.colours[new.colour as usize] |= bit;
+            self.hash ^= zobrist::piece(new, i);
+        }
+        self.squares[i] = square;
+    }
+    fn move_square(&mut self, from: usize, to: usize) {
+        let piece = self.squares[from];
+        self.set_square(from, None);
+        self.set_square(to, piece);
+    }
+    // every legal move for the active colour
+    // includes castling, en passant and one move per promotion piece
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.position()
+            .generate_legal_moves()
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect()
+    }
+    // a copy of the position alone, without the game's history, for trying moves out on
+    fn position(&self) -> Board {
+        Board {
+            squares: self.squares.clone(),
+            position_history: vec![],
+            history: vec![],
+            redo_stack: vec![],
+            ..*self
+        }
+    }
+    // legal moves with their move type, ready for make_move()
+    pub fn generate_legal_moves(&mut self) -> Vec<(Move, MoveType)> {
+        let moves = self.pseudo_legal_moves();
+        self.retain_legal(moves)
+    }
+    // legal captures and promotions, the moves that change the material on the board
+    pub fn generate_legal_captures(&mut self) -> Vec<(Move, MoveType)> {
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|(_, move_type)| {
+            matches!(
+                move_type,
+                MoveType::Capture
+                    | MoveType::EnPassant(_)
+                    | MoveType::PromotionPush
+                    | MoveType::PromotionCapture
+            )
+        });
+        self.retain_legal(moves)
+    }
+    fn retain_legal(&mut self, mut moves: Vec<(Move, MoveType)>) -> Vec<(Move, MoveType)> {
+        let colour = self.active_colour;
+        moves.retain(|(mv, move_type)| {
+            let undo = self.make_move(mv, *move_type);
+            let legal = !self.king_attacked(colour);
+            self.unmake_move(undo);
+            legal
+        });
+        moves
+    }
+    // every move for the active colour that follows the piece movement rules, with its move type
+    // moves that leave the king in check are included, see legal_moves()
+    // promotions are expanded into one move per piece
+    pub fn pseudo_legal_moves(&self) -> Vec<(Move, MoveType)> {
+        let own = self.colours[self.active_colour as usize];
+        let occupied = self.colours[0] | self.colours[1];
+        let mut moves = Vec::with_capacity(48);
+        for i0 in bitboard::squares(own) {
+            let targets = match self.squares[i0].unwrap().piece_type {
+                PieceType::Pawn => {
+                    self.pawn_moves(i0, &mut moves);
+                    continue;
+                }
+                PieceType::Knight => KNIGHT_ATTACKS[i0],
+                PieceType::Bishop => bishop_attacks(i0, occupied),
+                PieceType::Rook => rook_attacks(i0, occupied),
+                PieceType::Queen => queen_attacks(i0, occupied),
+                PieceType::King => {
+                    self.castling_moves(i0, &mut moves);
+                    KING_ATTACKS[i0]
+                }
+            };
+            for i in bitboard::squares(targets & !own) {
+                let move_type = match occupied & 1 << i {
+                    0 => MoveType::Regular,
+                    _ => MoveType::Capture,
+                };
+                push_move(&mut moves, i0, i, move_type);
+            }
+        }
+        moves
+    }
+    fn pawn_moves(&self, i0: usize, moves: &mut Vec<(Move, MoveType)>) {
+        let colour = self.active_colour;
+        let them = self.colours[colour.opposite() as usize];
+        let empty = !(self.colours[0] | self.colours[1]);
+        let from: Bitboard = 1 << i0;
+        // one square forwards, and a second from the starting rank
+        let forward = |bb: Bitboard| match colour {
+            Colour::White => bb << 8,
+            Colour::Black => bb >> 8,
+        };
+        let (start_rank, promotion_rank) = match colour {
+            Colour::White => (RANK_2, RANK_8),
+            Colour::Black => (RANK_7, RANK_1),
+        };
+        let single = forward(from) & empty;
+        if single & promotion_rank != 0 {
+            push_move(
+                moves,
+                i0,
+                single.trailing_zeros() as usize,
+                MoveType::PromotionPush,
+            );
+        } else if single != 0 {
+            let target = single.trailing_zeros() as usize;
+            push_move(moves, i0, target, MoveType::Regular);
+            let double = forward(single) & empty;
+            if from & start_rank != 0 && double != 0 {
+                let i = double.trailing_zeros() as usize;
+                push_move(moves, i0, i, MoveType::DoublePush(target));
+            }
+        }
+
+        let attacks = PAWN_ATTACKS[colour as usize][i0];
+        for i in bitboard::squares(attacks & them) {
+            let move_type = match promotion_rank & 1 << i {
+                0 => MoveType::Capture,
+                _ => MoveType::PromotionCapture,
+            };
+            push_move(moves, i0, i, move_type);
+        }
+        if let Some(target) = self.en_passant_target_square {
+            let target: usize = target.into();
+            if attacks & empty & 1 << target != 0 {
+                // captured pawn sits beside the moving pawn, behind the target square
+                let captured = match colour {
+                    Colour::White => target - 8,
+                    Colour::Black => target + 8,
+                };
+                push_move(moves, i0, target, MoveType::EnPassant(captured));
+            }
+        }
+    }
+    // castling needs the right, the rook on its original square, nothing between them and
+    // a king that doesn't start on, pass through or land on an attacked square
+    fn castling_moves(&self, i0: usize, moves: &mut Vec<(Move, MoveType)>) {
+        let colour = self.active_colour;
+        let (king_side, queen_side, king_start) = match colour {
+            Colour::White => (self.castling_rights.k_w, self.castling_rights.q_w, 4usize),
+            Colour::Black => (self.castling_rights.k_b, self.castling_rights.q_b, 60usize),
+        };
+        if i0 != king_start || !(king_side || queen_side) {
+            return;
+        }
+        let occupied = self.colours[0] | self.colours[1];
+        let rooks = self.pieces[PieceType::Rook as usize] & self.colours[colour as usize];
+        let is_safe = |i: usize| !self.is_attacked(i, colour.opposite());
+        if !is_safe(king_start) {
+            return;
+        }
+        if king_side
+            && rooks & 1 << (king_start + 3) != 0
+            && occupied & 0b11 << (king_start + 1) == 0
+            && is_safe(king_start + 1)
+            && is_safe(king_start + 2)
+        {
+            push_move(moves, i0, king_start + 2, MoveType::CastleKingSide);
+        }
+        // b, c and d files empty, only c and d need to be safe
+        if queen_side
+            && rooks & 1 << (king_start - 4) != 0
+            && occupied & 0b111 << (king_start - 3) == 0
+            && is_safe(king_start - 1)
+            && is_safe(king_start - 2)
+        {
+            push_move(moves, i0, king_start - 2, MoveType::CastleQueenSide);
+        }
+    }
+    // can any piece of colour `by` capture on square i?
+    // a piece of each type standing on i attacks the squares that piece type could attack it from
+    fn is_attacked(&self, i: usize, by: Colour) -> bool {
+        let attackers = self.colours[by as usize];
+        let occupied = self.colours[0] | self.colours[1];
+        let piece = |piece_type: PieceType| self.pieces[piece_type as usize] & attackers;
+        let queens = piece(PieceType::Queen);
+        PAWN_ATTACKS[by.opposite() as usize][i] & piece(PieceType::Pawn) != 0
+            || KNIGHT_ATTACKS[i] & piece(PieceType::Knight) != 0
+            || KING_ATTACKS[i] & piece(PieceType::King) != 0
+            || bishop_attacks(i, occupied) & (piece(PieceType::Bishop) | queens) != 0
+            || rook_attacks(i, occupied) & (piece(PieceType::Rook) | queens) != 0
+    }
+    // is the king of the given colour attacked? positions set up for analysis might not have one
+    fn king_attacked(&self, colour: Colour) -> bool {
+        let king = self.pieces[PieceType::King as usize] & self.colours[colour as usize];
+        king != 0 && self.is_attacked(king.trailing_zeros() as usize, colour.opposite())
+    }
+    // the square by square generator from move_logic
+    // kept alongside the bitboard one to check it against and to compare their speed
+    pub(crate) fn generate_mailbox_moves(&self) -> Vec<(Move, MoveType)> {
+        let mut moves = vec![];
+        for (i0, square) in self.squares.iter().enumerate() {
+            match square {
+                Some(piece) if piece.colour == self.active_colour => {}
+                _ => continue,
+            }
+            let mut piece_moves: Vec<(usize, MoveType)> = vec![];
+            move_logic::find_legal_moves(
+                &self.squares,
+                &mut piece_moves,
+                i0,
+                &self.en_passant_target_square,
+                &self.castling_rights,
+            );
+            for (i, move_type) in piece_moves {
+                let promotions = match move_type {
+                    MoveType::PromotionPush | MoveType::PromotionCapture => vec![
+                        Some(PieceType::Queen),
+                        Some(PieceType::Rook),
+                        Some(PieceType::Bishop),
+                        Some(PieceType::Knight),
+                    ],
+                    _ => vec![None],
+                };
+                for promotion in promotions {
+                    let mv = Move {
+                        from: i0.try_into().unwrap(),
+                        destination: i.try_into().unwrap(),
+                        promotion,
+                    };
+                    moves.push((mv, move_type));
+                }
+            }
+        }
+        moves
+    }
+    // legal moves from the mailbox generator, see generate_legal_moves()
+    pub(crate) fn generate_mailbox_legal_moves(&mut self) -> Vec<(Move, MoveType)> {
+        let colour = self.active_colour;
+        let mut moves = self.generate_mailbox_moves();
+        moves.retain(|(mv, move_type)| {
+            let undo = self.make_move(mv, *move_type);
+            let legal = !move_logic::in_check(&colour, &self.squares);
+            self.unmake_move(undo);
+            legal
+        });
+        moves
     }
     // check that move is legal
-    fn validate_move(&self, i0: usize, i: usize) -> Result<MoveType, BoardError> {
+    fn validate_move(&mut self, i0: usize, i: usize) -> Result<MoveType, BoardError> {
         // check piece is exists
         let piece = match &self.squares[i0] {
             Some(p) => p,
@@ -154,27 +855,26 @@ impl Board {
         if piece.colour != self.active_colour {
             return Err(BoardError::WrongColour);
         }
-        // OK! find all legal moves from squares[i0]
-        let mut legal_moves: Vec<(usize, MoveType)> = vec![];
-
-        move_logic::find_legal_moves(
-            &self.squares,
-            &mut legal_moves,
-            i0,
-            &self.en_passant_target_square,
-        );
-        // for (x, m) in &move_vec {
-        //     //println!("[{}] {:?}", x, m);
-        // }
-        // check if index is in vec of legal moves
-        match legal_moves.into_iter().find(|(x, _)| *x == i) {
-            Some((_, m)) => Ok(m),
-            None => Err(BoardError::InvalidMove),
+        // OK! find the moves from squares[i0] to squares[i], one per promotion piece
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|(mv, _)| usize::from(mv.from) == i0 && usize::from(mv.destination) == i);
+        let Some(&(_, move_type)) = moves.first() else {
+            return Err(BoardError::InvalidMove);
+        };
+        // the mover's king can't be left attacked (incl. by pawns removed by en passant)
+        match self.retain_legal(moves).is_empty() {
+            true => Err(BoardError::InCheck),
+            false => Ok(move_type),
         }
     }
 
-    #[allow(dead_code)]
-    fn export_fen(&self) -> Result<String, BoardError> {
+    // the fen without the half-move clock and full-move number
+    pub fn export_epd(&self) -> Result<String, BoardError> {
+        let fen = self.export_fen()?;
+        Ok(fen.split(' ').take(4).collect::<Vec<&str>>().join(" "))
+    }
+
+    pub fn export_fen(&self) -> Result<String, BoardError> {
         let mut piece_data: Vec<String> = vec![];
 
         for rank in 0..8usize {
@@ -241,233 +941,511 @@ impl FromStr for Board {
         // accept only valid fen strings and return a board
         // startpos = rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
         // possible pos = rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2
-        let mut fen_it = s.split_ascii_whitespace();
-        let fen_piece_data = match fen_it.next() {
-            Some(str) => str,
-            None => return Err(BoardError::FenError("no piece data found".to_string())),
-        };
+        Board::parse_fen(s, true)
+    }
+}
 
-        let active_colour = match fen_it.next() {
-            Some(str) => str.parse::<Colour>()?,
-            None => return Err(BoardError::FenError("no colour data found".to_string())),
-        };
-        let castling_rights = match fen_it.next() {
-            Some(str) => str.parse::<CastlingRights>()?,
-            None => {
-                return Err(BoardError::FenError(
-                    "no castling rights data found".to_string(),
-                ))
+// extended position description: the first four fen fields followed by operations,
+// e.g. 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+// the move counters come from the hmvc and fmvn opcodes when they are given
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board,
+    // opcode and operands of each operation, in order
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+    // the moves listed by a move opcode such as bm (best move) or am (avoid move)
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>, BoardError> {
+        self.operands(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|operand| self.board.parse_move(operand))
+            .collect()
+    }
+    // whether mv is one of the best moves and none of the moves to avoid
+    // None when the position has neither a bm nor an am operation
+    pub fn check_move(&self, mv: &Move) -> Result<Option<bool>, BoardError> {
+        let best = self.moves("bm")?;
+        let avoid = self.moves("am")?;
+        if best.is_empty() && avoid.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            (best.is_empty() || best.contains(mv)) && !avoid.contains(mv),
+        ))
+    }
+}
+
+impl FromStr for Epd {
+    type Err = BoardError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim();
+        let mut fields: Vec<&str> = vec![];
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end > 0 {
+                fields.push(&rest[..end]);
             }
+            rest = rest[end..].trim_start();
+        }
+        let operations = parse_epd_operations(rest)?;
+        let counter = |opcode: &str, default: &str| {
+            operations
+                .iter()
+                .find(|(op, _)| op == opcode)
+                .and_then(|(_, operands)| operands.first().cloned())
+                .unwrap_or(default.to_string())
         };
-        let en_passant_target_square = match fen_it.next() {
-            Some(str) => {
-                if str == '-'.to_string() {
-                    None
+        // missing position fields are reported by the fen parser
+        let fen = match fields.len() {
+            4 => format!(
+                "{} {} {}",
+                fields.join(" "),
+                counter("hmvc", "0"),
+                counter("fmvn", "1")
+            ),
+            _ => fields.join(" "),
+        };
+        let board = Board::from_str(&fen)?;
+        Ok(Epd { board, operations })
+    }
+}
+
+// position fields without the move counters, then each operation ended by ';'
+impl Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.board.export_epd().map_err(|_| fmt::Error)?)?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            // comments and ids are strings, anything else only needs quotes if it has spaces
+            let string = opcode == "id"
+                || (opcode.len() == 2
+                    && opcode.starts_with('c')
+                    && opcode.ends_with(|c: char| c.is_ascii_digit()));
+            for operand in operands {
+                if string
+                    || operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == ';')
+                {
+                    write!(f, " \"{}\"", operand)?;
                 } else {
-                    Some(str.parse::<Coordinate>().unwrap())
+                    write!(f, " {}", operand)?;
                 }
             }
-            None => {
-                return Err(BoardError::FenError(
-                    "no en passant target data found".to_string(),
-                ))
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+// opcode followed by any number of operands and a ';', operands may be "quoted strings"
+fn parse_epd_operations(s: &str) -> Result<Vec<(String, Vec<String>)>, BoardError> {
+    let mut operations = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut chars = s.chars();
+    loop {
+        match chars.next() {
+            Some('"') => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => string.push(c),
+                        None => {
+                            return Err(BoardError::EpdError(format!(
+                                "string \"{} is never closed",
+                                string
+                            )))
+                        }
+                    }
+                }
+                words.push(string);
             }
-        };
-        let half_move_clock = match fen_it.next() {
-            Some(str) => str.parse::<usize>().unwrap(),
-            None => {
-                return Err(BoardError::FenError(
-                    "no half move clock data found".to_string(),
-                ))
+            Some(c) if c == ';' || c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if c == ';' {
+                    if words.is_empty() {
+                        return Err(BoardError::EpdError("operation has no opcode".to_string()));
+                    }
+                    let opcode = words.remove(0);
+                    let valid = opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+                        && opcode
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if !valid {
+                        return Err(BoardError::EpdError(format!("invalid opcode '{}'", opcode)));
+                    }
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
             }
-        };
-        let full_move_number = match fen_it.next() {
-            Some(str) => str.parse::<usize>().unwrap(),
-            None => {
-                return Err(BoardError::FenError(
-                    "no full move number data found".to_string(),
-                ))
+            Some(c) => word.push(c),
+            None => break,
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        return Err(BoardError::EpdError(format!(
+            "operation '{}' is missing its ';'",
+            words.join(" ")
+        )));
+    }
+    Ok(operations)
+}
+
+// add a move to the list, with one move per promotion piece for promotions
+fn push_move(moves: &mut Vec<(Move, MoveType)>, i0: usize, i: usize, move_type: MoveType) {
+    let mut mv = Move {
+        from: i0.try_into().unwrap(),
+        destination: i.try_into().unwrap(),
+        promotion: None,
+    };
+    match move_type {
+        MoveType::PromotionPush | MoveType::PromotionCapture => {
+            for piece_type in [
+                PieceType::Queen,
+                PieceType::Rook,
+                PieceType::Bishop,
+                PieceType::Knight,
+            ] {
+                mv.promotion = Some(piece_type);
+                moves.push((mv, move_type));
             }
-        };
+        }
+        _ => moves.push((mv, move_type)),
+    }
+}
+
+// error for fen field number `field` (1 == piece placement ... 6 == full-move number)
+fn fen_error(field: usize, reason: impl Into<String>) -> BoardError {
+    BoardError::FenError {
+        field,
+        reason: reason.into(),
+    }
+}
 
-        // check that the fen iterator has been exhausted
-        let extra_args = fen_it.next();
-        if extra_args.is_some() {
-            return Err(BoardError::FenError(format!(
-                "too many arguments! Extra args found: {}",
-                extra_args.unwrap()
-            )));
+impl Board {
+    // strict parsing also rejects positions that can't arise in a real game (see validate_position)
+    // lenient parsing only checks the syntax and allows the move counters to be left out,
+    // for setting up analysis positions
+    fn parse_fen(s: &str, strict: bool) -> Result<Board, BoardError> {
+        let fields: Vec<&str> = s.split_ascii_whitespace().collect();
+        let required_fields = if strict { 6 } else { 4 };
+        if fields.len() < required_fields {
+            let missing = [
+                "piece placement",
+                "active colour",
+                "castling rights",
+                "en passant target",
+                "half-move clock",
+                "full-move number",
+            ][fields.len()];
+            return Err(fen_error(fields.len() + 1, format!("no {} found", missing)));
+        }
+        // check that there is nothing after the full-move number
+        if let Some(extra_args) = fields.get(6) {
+            return Err(fen_error(
+                7,
+                format!("too many arguments! Extra args found: {}", extra_args),
+            ));
         }
 
-        // Board pieces
+        let squares = Self::parse_piece_placement(fields[0], strict)?;
+        let active_colour = fields[1]
+            .parse::<Colour>()
+            .map_err(|_| fen_error(2, format!("expected 'w' or 'b', found '{}'", fields[1])))?;
+        let castling_rights = fields[2].parse::<CastlingRights>().map_err(|_| {
+            fen_error(
+                3,
+                format!("expected '-' or a subset of 'KQkq', found '{}'", fields[2]),
+            )
+        })?;
+        let en_passant_target_square = match fields[3] {
+            "-" => None,
+            str => {
+                let coord = str
+                    .parse::<Coordinate>()
+                    .map_err(|e| fen_error(4, format!("'{}': {}", str, e)))?;
+                if coord.rank != 3 && coord.rank != 6 {
+                    return Err(fen_error(4, format!("{} is not on rank 3 or 6", coord)));
+                }
+                Some(coord)
+            }
+        };
+        let half_move_clock = match fields.get(4) {
+            Some(str) => str
+                .parse::<usize>()
+                .map_err(|e| fen_error(5, format!("'{}': {}", str, e)))?,
+            None => 0,
+        };
+        let full_move_number = match fields.get(5) {
+            Some(str) => str
+                .parse::<usize>()
+                .map_err(|e| fen_error(6, format!("'{}': {}", str, e)))?,
+            None => 1,
+        };
+
+        let mut board = Board {
+            squares: vec![None; 64],
+            pieces: [0; 6],
+            colours: [0; 2],
+            hash: 0,
+            active_colour,
+            castling_rights,
+            en_passant_target_square,
+            half_move_clock,
+            full_move_number,
+            game_state: GameState::Active,
+            position_history: vec![],
+            history: vec![],
+            redo_stack: vec![],
+        };
+        for (i, square) in squares.into_iter().enumerate() {
+            board.set_square(i, square);
+        }
+        board.hash = board.zobrist_hash();
+        if strict {
+            board.validate_position()?;
+        }
+        board.position_history.push(board.hash());
+        board.update_game_state();
+        Ok(board)
+    }
 
+    // squares in board order (a1 first) from the first fen field
+    fn parse_piece_placement(data: &str, strict: bool) -> Result<Vec<Option<Piece>>, BoardError> {
+        let ranks: Vec<&str> = data.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(fen_error(
+                1,
+                format!("expected 8 ranks separated by '/', found {}", ranks.len()),
+            ));
+        }
         let mut squares: Vec<Option<Piece>> = Vec::new();
         // reverse iterator to fill pieces in correct order (rank 1 to rank 8)
-        for rank in fen_piece_data.split('/').rev() {
+        for rank in ranks.into_iter().rev() {
             // create temporary rank data to fill board.squares
             let mut board_rank_data: Vec<Option<Piece>> = Vec::new();
-            let pieces = rank.chars();
-            for piece in pieces {
-                if piece.is_alphabetic() {
-                    board_rank_data.push(Some(Piece::try_from(piece)?));
-                } else if piece.is_numeric() {
-                    let n = piece.to_string().parse::<usize>()?;
-                    for _ in 0..n {
-                        board_rank_data.push(None);
+            let mut previous_was_digit = false;
+            for c in rank.chars() {
+                match c {
+                    '1'..='8' => {
+                        if strict && previous_was_digit {
+                            return Err(fen_error(
+                                1,
+                                format!("consecutive empty square counts in rank '{}'", rank),
+                            ));
+                        }
+                        let n = c.to_digit(10).unwrap() as usize;
+                        board_rank_data.extend(std::iter::repeat_n(None, n));
+                        previous_was_digit = true;
+                    }
+                    _ => {
+                        let piece = Piece::try_from(c).map_err(|_| {
+                            fen_error(1, format!("invalid character '{}' in rank '{}'", c, rank))
+                        })?;
+                        board_rank_data.push(Some(piece));
+                        previous_was_digit = false;
                     }
                 }
             }
             if board_rank_data.len() != 8 {
-                return Err(BoardError::FenError(format!(
-                    "rank length must equal 8. Current rank == {}",
-                    rank
-                )));
-            } else {
-                squares.append(&mut board_rank_data)
+                return Err(fen_error(
+                    1,
+                    format!("rank length must equal 8. Current rank == {}", rank),
+                ));
             }
+            squares.append(&mut board_rank_data)
         }
+        Ok(squares)
+    }
 
-        // parse everything else
+    // reject positions that can't be reached from the starting position
+    fn validate_position(&self) -> Result<(), BoardError> {
+        for colour in [Colour::White, Colour::Black] {
+            let count = |piece_type: PieceType| {
+                self.squares
+                    .iter()
+                    .flatten()
+                    .filter(|p| p.colour == colour && p.piece_type == piece_type)
+                    .count()
+            };
+            let name = match colour {
+                Colour::White => "white",
+                Colour::Black => "black",
+            };
+            let kings = count(PieceType::King);
+            if kings != 1 {
+                return Err(fen_error(
+                    1,
+                    format!("{} must have exactly one king, found {}", name, kings),
+                ));
+            }
+            let pawns = count(PieceType::Pawn);
+            if pawns > 8 {
+                return Err(fen_error(1, format!("{} has {} pawns", name, pawns)));
+            }
+            // every piece beyond the starting set must have come from a promoted pawn
+            let promoted = count(PieceType::Queen).saturating_sub(1)
+                + count(PieceType::Rook).saturating_sub(2)
+                + count(PieceType::Bishop).saturating_sub(2)
+                + count(PieceType::Knight).saturating_sub(2);
+            if promoted + pawns > 8 {
+                return Err(fen_error(
+                    1,
+                    format!(
+                        "{} has {} promoted pieces but only {} pawns are missing",
+                        name,
+                        promoted,
+                        8 - pawns
+                    ),
+                ));
+            }
+        }
+        for (i, square) in self.squares.iter().enumerate() {
+            if let Some(p) = square {
+                if p.piece_type == PieceType::Pawn && !(8..56).contains(&i) {
+                    return Err(fen_error(
+                        1,
+                        format!("pawn on {}", Coordinate::try_from(i)?),
+                    ));
+                }
+            }
+        }
 
-        Ok(Board {
-            squares,
-            active_colour,
-            castling_rights,
-            en_passant_target_square,
-            half_move_clock,
-            full_move_number,
-        })
+        let mut inactive_colour = self.active_colour;
+        inactive_colour.change_colour();
+        if self.king_attacked(inactive_colour) {
+            return Err(fen_error(
+                2,
+                format!("{:?} is in check but it is not their move", inactive_colour),
+            ));
+        }
+
+        // each right needs the king and that rook on their starting squares
+        let rights = [
+            (self.castling_rights.k_w, 'K', 4, 'R', 7),
+            (self.castling_rights.q_w, 'Q', 4, 'R', 0),
+            (self.castling_rights.k_b, 'k', 60, 'r', 63),
+            (self.castling_rights.q_b, 'q', 60, 'r', 56),
+        ];
+        for (right, name, king, rook_char, rook) in rights {
+            let king_char = if rook_char == 'R' { 'K' } else { 'k' };
+            let in_place = |i: usize, c: char| self.squares[i].map(char::from) == Some(c);
+            if right && !(in_place(king, king_char) && in_place(rook, rook_char)) {
+                return Err(fen_error(
+                    3,
+                    format!(
+                        "'{}' needs the king and rook on their starting squares",
+                        name
+                    ),
+                ));
+            }
+        }
+
+        if let Some(coord) = &self.en_passant_target_square {
+            let target: usize = coord.into();
+            // the pawn that just moved two squares, the square it passed and the square it left
+            let (expected_rank, pawn, behind, origin) = match self.active_colour {
+                Colour::White => (6, target - 8, target, target + 8),
+                Colour::Black => (3, target + 8, target, target - 8),
+            };
+            let mut moved_colour = self.active_colour;
+            moved_colour.change_colour();
+            let pawn_in_place = matches!(
+                self.squares[pawn],
+                Some(p) if p.piece_type == PieceType::Pawn && p.colour == moved_colour
+            );
+            if coord.rank != expected_rank
+                || !pawn_in_place
+                || self.squares[behind].is_some()
+                || self.squares[origin].is_some()
+            {
+                return Err(fen_error(
+                    4,
+                    format!("{} does not follow a double pawn push", coord),
+                ));
+            }
+            if self.half_move_clock != 0 {
+                return Err(fen_error(5, "must be 0 directly after a double pawn push"));
+            }
+        }
+
+        if self.full_move_number == 0 {
+            return Err(fen_error(6, "must start at 1"));
+        }
+        Ok(())
     }
 }
 
+// how pieces are drawn on the board
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum RenderStyle {
+    /// Coloured letters
+    Ascii,
+    /// Coloured chess symbols
+    #[default]
+    Unicode,
+    /// Plain letters, for terminals without colour support
+    NoColour,
+}
+
 // basic ascii chess board
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let v: Vec<String> = self
-            .squares
-            .iter()
-            .map(|opt| match opt {
-                Some(piece) => format!("{}", piece),
-                None => " ".to_string(),
-            })
-            .collect();
-        let row1 = format!(
-            "1 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]
-        );
-        let row2 = format!(
-            "2 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[8], v[9], v[10], v[11], v[12], v[13], v[14], v[15]
-        );
-        let row3 = format!(
-            "3 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[16], v[17], v[18], v[19], v[20], v[21], v[22], v[23]
-        );
-        let row4 = format!(
-            "4 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[24], v[25], v[26], v[27], v[28], v[29], v[30], v[31]
-        );
-        let row5 = format!(
-            "5 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[32], v[33], v[34], v[35], v[36], v[37], v[38], v[39]
-        );
-        let row6 = format!(
-            "6 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[40], v[41], v[42], v[43], v[44], v[45], v[46], v[47]
-        );
-        let row7 = format!(
-            "7 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[48], v[49], v[50], v[51], v[52], v[53], v[54], v[55]
-        );
-        let row8 = format!(
-            "8 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[56], v[57], v[58], v[59], v[60], v[61], v[62], v[63]
-        );
-        // set background colour
-        //write!(f, "\x1B[106m")?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row8)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row7)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row6)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row5)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row4)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row3)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row2)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "{}", row1)?;
-        writeln!(f, "  —------------------------------------------------")?;
-        writeln!(f, "     A     B     C     D     E     F     G     H   ")
+        write!(f, "{}", self.draw(|piece| piece.to_string(), false))
     }
 }
 
 impl Board {
-    pub fn display_unicode(&self) {
-        let v: Vec<String> = self
-            .squares
-            .iter()
-            .map(|opt| match opt {
-                Some(piece) => format!("{}", piece.unicode_symbol()),
-                None => " ".to_string(),
-            })
-            .collect();
-        let row1 = format!(
-            "1 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]
-        );
-        let row2 = format!(
-            "2 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[8], v[9], v[10], v[11], v[12], v[13], v[14], v[15]
-        );
-        let row3 = format!(
-            "3 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[16], v[17], v[18], v[19], v[20], v[21], v[22], v[23]
-        );
-        let row4 = format!(
-            "4 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[24], v[25], v[26], v[27], v[28], v[29], v[30], v[31]
-        );
-        let row5 = format!(
-            "5 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[32], v[33], v[34], v[35], v[36], v[37], v[38], v[39]
-        );
-        let row6 = format!(
-            "6 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[40], v[41], v[42], v[43], v[44], v[45], v[46], v[47]
-        );
-        let row7 = format!(
-            "7 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[48], v[49], v[50], v[51], v[52], v[53], v[54], v[55]
-        );
-        let row8 = format!(
-            "8 |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |  {}  |",
-            v[56], v[57], v[58], v[59], v[60], v[61], v[62], v[63]
-        );
-        // set background colour
-        //write!(f, "\x1B[106m")?;
-        println!("  —------------------------------------------------");
-        println!("{}", row8);
-        println!("  —------------------------------------------------");
-        println!("{}", row7);
-        println!("  —------------------------------------------------");
-        println!("{}", row6);
-        println!("  —------------------------------------------------");
-        println!("{}", row5);
-        println!("  —------------------------------------------------");
-        println!("{}", row4);
-        println!("  —------------------------------------------------");
-        println!("{}", row3);
-        println!("  —------------------------------------------------");
-        println!("{}", row2);
-        println!("  —------------------------------------------------");
-        println!("{}", row1);
-        println!("  —------------------------------------------------");
-        println!("     A     B     C     D     E     F     G     H   ");
+    // flipped boards have black at the bottom
+    pub fn render(&self, style: RenderStyle, flipped: bool) -> String {
+        match style {
+            RenderStyle::Ascii => self.draw(|piece| piece.to_string(), flipped),
+            RenderStyle::Unicode => self.draw(|piece| piece.unicode_symbol(), flipped),
+            RenderStyle::NoColour => self.draw(|piece| char::from(*piece).to_string(), flipped),
+        }
+    }
+    // board grid with rank 8 at the top, or rank 1 when flipped
+    fn draw(&self, symbol: impl Fn(&Piece) -> String, flipped: bool) -> String {
+        let border = "  —------------------------------------------------\n";
+        let mut ranks: Vec<usize> = (0..8).rev().collect();
+        let mut files: Vec<usize> = (0..8).collect();
+        if flipped {
+            ranks.reverse();
+            files.reverse();
+        }
+        let mut output = border.to_string();
+        for rank in ranks {
+            output.push_str(&format!("{} |", rank + 1));
+            for file in &files {
+                let square = match &self.squares[rank * 8 + file] {
+                    Some(piece) => symbol(piece),
+                    None => " ".to_string(),
+                };
+                output.push_str(&format!("  {}  |", square));
+            }
+            output.push('\n');
+            output.push_str(border);
+        }
+        output.push_str("  ");
+        for file in files {
+            output.push_str(&format!("   {}  ", (b'A' + file as u8) as char));
+        }
+        output.push_str(" \n");
+        output
     }
 }
 // debug information
@@ -481,7 +1459,8 @@ impl fmt::Debug for Board {
             self.en_passant_target_square
         )?;
         writeln!(f, "half-moves: '{:?}'", self.half_move_clock)?;
-        write!(f, "moves: '{:?}'", self.full_move_number)
+        writeln!(f, "moves: '{:?}'", self.full_move_number)?;
+        write!(f, "status: '{:?}'", self.game_state)
     }
 }
 /* ------- T E S T S ---------*/
@@ -523,4 +1502,962 @@ mod tests {
             assert_eq!(fen, board.export_fen().unwrap());
         }
     }
+
+    fn play(board: &mut Board, mv: &str) -> Result<(), BoardError> {
+        board.process_move(&Move::from_str(mv).unwrap())
+    }
+
+    fn piece_at(board: &Board, coord: &str) -> Option<char> {
+        let i: usize = Coordinate::from_str(coord).unwrap().into();
+        board.squares[i].map(|p| p.into())
+    }
+
+    #[test]
+    fn castle_king_side() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "e1g1").unwrap();
+        assert_eq!(piece_at(&board, "g1"), Some('K'));
+        assert_eq!(piece_at(&board, "f1"), Some('R'));
+        assert_eq!(piece_at(&board, "e1"), None);
+        assert_eq!(piece_at(&board, "h1"), None);
+        play(&mut board, "e8g8").unwrap();
+        assert_eq!(piece_at(&board, "g8"), Some('k'));
+        assert_eq!(piece_at(&board, "f8"), Some('r'));
+        assert_eq!(piece_at(&board, "e8"), None);
+        assert_eq!(piece_at(&board, "h8"), None);
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut board = Board::startpos();
+        assert_eq!(play(&mut board, "e3e4"), Err(BoardError::EmptySquare));
+        assert_eq!(play(&mut board, "e7e5"), Err(BoardError::WrongColour));
+        assert_eq!(play(&mut board, "e2e5"), Err(BoardError::InvalidMove));
+        // the knight is pinned
+        let fen = "4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e2c3"), Err(BoardError::InCheck));
+        // taking en passant would uncover the rook
+        let fen = "4k3/8/8/r2pP2K/8/8/8/8 w - d6 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e5d6"), Err(BoardError::InCheck));
+        play(&mut board, "e5e6").unwrap();
+        // a promotion needs a piece
+        let fen = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "b7b8"), Err(BoardError::PromotionError));
+        play(&mut board, "b7b8n").unwrap();
+        assert_eq!(piece_at(&board, "b8"), Some('N'));
+    }
+
+    #[test]
+    fn castle_queen_side() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "e1c1").unwrap();
+        assert_eq!(piece_at(&board, "c1"), Some('K'));
+        assert_eq!(piece_at(&board, "d1"), Some('R'));
+        assert_eq!(piece_at(&board, "e1"), None);
+        assert_eq!(piece_at(&board, "a1"), None);
+        play(&mut board, "e8c8").unwrap();
+        assert_eq!(piece_at(&board, "c8"), Some('k'));
+        assert_eq!(piece_at(&board, "d8"), Some('r'));
+        assert_eq!(piece_at(&board, "e8"), None);
+        assert_eq!(piece_at(&board, "a8"), None);
+    }
+
+    #[test]
+    fn castle_requires_rights() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e1c1"), Err(BoardError::InvalidMove));
+
+        // only the queen side right remains
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e1c1"), Ok(()));
+    }
+
+    #[test]
+    fn castle_requires_rook() {
+        // rights in the fen are stale, there is no rook on a1
+        let fen = "4k3/8/8/8/8/8/8/4K2R w KQ - 0 1";
+        let mut board = Board::new_lenient(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1c1"), Err(BoardError::InvalidMove));
+        // a knight is not a rook
+        let fen = "4k3/8/8/8/8/8/7P/4K2N w K - 0 1";
+        let mut board = Board::new_lenient(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+    }
+
+    #[test]
+    fn castle_blocked() {
+        let fen = "4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        // b1 must be empty even though the king never crosses it
+        assert_eq!(play(&mut board, "e1c1"), Err(BoardError::InvalidMove));
+    }
+
+    #[test]
+    fn castle_out_of_check() {
+        let fen = "4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e1c1"), Err(BoardError::InvalidMove));
+    }
+
+    #[test]
+    fn castle_through_attacked_square() {
+        // f1 attacked by rook
+        let fen = "5rk1/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e1c1"), Ok(()));
+
+        // d8 attacked by knight on c6
+        let fen = "r3k2r/8/2N5/8/8/8/8/4K3 b kq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e8c8"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e8g8"), Ok(()));
+    }
+
+    #[test]
+    fn castle_into_attacked_square() {
+        // g1 attacked by rook, c1 attacked by pawn on b2
+        let fen = "1k4r1/8/8/8/8/8/1p6/R3K2R w KQ - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1g1"), Err(BoardError::InvalidMove));
+        assert_eq!(play(&mut board, "e1c1"), Err(BoardError::InvalidMove));
+    }
+
+    #[test]
+    fn castle_queen_side_with_b_file_attacked() {
+        // the king never crosses b1, so an attack on it doesn't matter
+        let fen = "1r4k1/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(play(&mut board, "e1c1"), Ok(()));
+        assert_eq!(piece_at(&board, "d1"), Some('R'));
+    }
+
+    #[test]
+    fn fen_after_moves() {
+        let mut board = Board::startpos();
+        play(&mut board, "e2e4").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        play(&mut board, "c7c5").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2"
+        );
+        // stale en passant target is cleared, quiet move increments the clock
+        play(&mut board, "g1f3").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        play(&mut board, "b8c6").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r1bqkbnr/pp1ppppp/2n5/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+        );
+        // pawn move resets the clock
+        play(&mut board, "d2d4").unwrap();
+        play(&mut board, "c5d4").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r1bqkbnr/pp1ppppp/2n5/8/3pP3/5N2/PPP2PPP/RNBQKB1R w KQkq - 0 4"
+        );
+        // capture with a piece resets the clock
+        play(&mut board, "f3d4").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r1bqkbnr/pp1ppppp/2n5/8/3NP3/8/PPP2PPP/RNBQKB1R b KQkq - 0 4"
+        );
+    }
+
+    #[test]
+    fn fen_round_trip_after_moves() {
+        let mut board = Board::startpos();
+        for mv in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"] {
+            play(&mut board, mv).unwrap();
+            let fen = board.export_fen().unwrap();
+            assert_eq!(fen, Board::new(fen.clone()).unwrap().export_fen().unwrap());
+        }
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4"
+        );
+    }
+
+    #[test]
+    fn castling_rights_lost_on_king_move() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "e1e2").unwrap();
+        play(&mut board, "e8c8").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "2kr3r/8/8/8/8/8/4K3/R6R w - - 2 2"
+        );
+    }
+
+    #[test]
+    fn castling_rights_lost_on_rook_move() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "h1h2").unwrap();
+        assert_eq!(board.castling_rights.to_string(), "Qkq");
+        play(&mut board, "a8a7").unwrap();
+        assert_eq!(board.castling_rights.to_string(), "Qk");
+        // moving the rook back doesn't restore the right
+        play(&mut board, "h2h1").unwrap();
+        play(&mut board, "a7a8").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r3k2r/8/8/8/8/8/8/R3K2R w Qk - 4 3"
+        );
+    }
+
+    #[test]
+    fn castling_rights_lost_on_rook_capture() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        // rook takes rook, both sides lose the right on that corner
+        play(&mut board, "a1a8").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "R3k2r/8/8/8/8/8/8/4K2R b Kk - 0 1"
+        );
+        // a knight captures the rook on h1
+        let fen = "r3k2r/8/8/8/8/8/5n2/R3K2R b KQkq - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "f2h1").unwrap();
+        assert_eq!(
+            board.export_fen().unwrap(),
+            "r3k2r/8/8/8/8/8/8/R3K2n w Qkq - 0 2"
+        );
+    }
+
+    fn move_strings(moves: Vec<Move>) -> Vec<String> {
+        let mut moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn legal_moves_startpos() {
+        let board = Board::startpos();
+        assert_eq!(board.legal_moves().len(), 20);
+        assert_eq!(board.pseudo_legal_moves().len(), 20);
+    }
+
+    #[test]
+    fn legal_moves_exclude_pinned_piece() {
+        // knight on e2 pinned by the rook on e8
+        let fen = "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(
+            move_strings(board.legal_moves()),
+            vec!["e1d1", "e1d2", "e1f1", "e1f2"]
+        );
+        assert_eq!(board.pseudo_legal_moves().len(), 10);
+    }
+
+    #[test]
+    fn legal_moves_promotions() {
+        let fen = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        let moves = move_strings(board.legal_moves());
+        for mv in [
+            "a7a8q", "a7a8r", "a7a8b", "a7a8n", "a7b8q", "a7b8r", "a7b8b", "a7b8n",
+        ] {
+            assert!(moves.contains(&mv.to_string()), "{}", mv);
+        }
+        assert!(!moves.contains(&"a7a8".to_string()));
+    }
+
+    #[test]
+    fn legal_moves_castling_and_en_passant() {
+        let fen = "4k3/8/8/3Pp3/8/8/8/R3K2R w KQ e6 0 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        let moves = move_strings(board.legal_moves());
+        for mv in ["e1g1", "e1c1", "d5e6"] {
+            assert!(moves.contains(&mv.to_string()), "{}", mv);
+        }
+        // every legal move can be played
+        for mv in board.legal_moves() {
+            let mut next = board.clone();
+            assert_eq!(next.process_move(&mv), Ok(()), "{}", mv);
+        }
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut board = Board::startpos();
+        for mv in ["f2f3", "e7e5", "g2g4"] {
+            play(&mut board, mv).unwrap();
+            assert_eq!(board.status(), GameState::Active);
+        }
+        play(&mut board, "d8h4").unwrap();
+        assert_eq!(board.status(), GameState::BlackWin);
+        // no more moves once the game is over
+        assert_eq!(play(&mut board, "e2e4"), Err(BoardError::GameOver));
+
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "a1a8").unwrap();
+        assert_eq!(board.status(), GameState::WhiteWin);
+    }
+
+    #[test]
+    fn stalemate() {
+        let fen = "7k/8/6Q1/8/8/8/8/K7 w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "g6f7").unwrap();
+        assert_eq!(board.status(), GameState::Stalemate);
+        assert_eq!(play(&mut board, "h8g8"), Err(BoardError::GameOver));
+    }
+
+    #[test]
+    fn status_from_fen() {
+        // already checkmated
+        let fen = "R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(board.status(), GameState::WhiteWin);
+        // already stalemated
+        let fen = "7k/5Q2/8/8/8/8/8/K7 b - - 1 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(board.status(), GameState::Stalemate);
+        // in check but can escape
+        let fen = "R5k1/5pp1/8/8/8/8/8/6K1 b - - 1 1";
+        let board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(board.status(), GameState::Active);
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let mut board = Board::startpos();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for mv in shuffle {
+            play(&mut board, mv).unwrap();
+        }
+        // startpos has been seen twice
+        assert_eq!(board.claimable_draw(), None);
+        assert_eq!(board.claim_draw(), Err(BoardError::NoDrawToClaim));
+        for mv in shuffle {
+            play(&mut board, mv).unwrap();
+        }
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        // claimable, but the game goes on until someone claims it
+        assert_eq!(board.status(), GameState::Active);
+        assert_eq!(board.claim_draw(), Ok(DrawReason::ThreefoldRepetition));
+        assert_eq!(
+            board.status(),
+            GameState::Draw(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(play(&mut board, "e2e4"), Err(BoardError::GameOver));
+    }
+
+    #[test]
+    fn fivefold_repetition() {
+        let mut board = Board::startpos();
+        for _ in 0..4 {
+            for mv in ["b1c3", "b8c6", "c3b1", "c6b8"] {
+                play(&mut board, mv).unwrap();
+            }
+        }
+        assert_eq!(
+            board.status(),
+            GameState::Draw(DrawReason::FivefoldRepetition)
+        );
+    }
+
+    #[test]
+    fn repetition_ignores_unusable_en_passant_target() {
+        // after 1. e4 the e3 target can't be used, so returning to this position repeats it
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        for _ in 0..2 {
+            for mv in ["g8f6", "g1f3", "f6g8", "f3g1"] {
+                play(&mut board, mv).unwrap();
+            }
+        }
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_move_rules() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 99 80";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(board.claimable_draw(), None);
+        play(&mut board, "a1a2").unwrap();
+        assert_eq!(board.claimable_draw(), Some(DrawReason::FiftyMoveRule));
+        assert_eq!(board.status(), GameState::Active);
+
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 149 80";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "a1a2").unwrap();
+        assert_eq!(
+            board.status(),
+            GameState::Draw(DrawReason::SeventyFiveMoveRule)
+        );
+
+        // checkmate on the last move takes priority
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 149 80";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        play(&mut board, "a1a8").unwrap();
+        assert_eq!(board.status(), GameState::WhiteWin);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let dead = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/5B2/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/5n2/8 w - - 0 1",
+            // bishops all on dark squares
+            "8/8/4k2b/8/8/3K4/8/2B5 w - - 0 1",
+        ];
+        for fen in dead {
+            let board = Board::new(fen.to_string()).unwrap();
+            assert_eq!(
+                board.status(),
+                GameState::Draw(DrawReason::InsufficientMaterial),
+                "{}",
+                fen
+            );
+        }
+        let alive = [
+            "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4NN2/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4Bn2/8 w - - 0 1",
+            // opposite coloured bishops
+            "8/8/4k1b1/8/8/3K4/8/2B5 w - - 0 1",
+        ];
+        for fen in alive {
+            let board = Board::new(fen.to_string()).unwrap();
+            assert_eq!(board.status(), GameState::Active, "{}", fen);
+        }
+        // capturing the last pawn ends the game
+        let fen = "8/8/8/5k2/3p4/4K3/8/8 w - - 0 1";
+        let mut board = Board::new(fen.to_string()).unwrap();
+        assert_eq!(board.status(), GameState::Active);
+        play(&mut board, "e3d4").unwrap();
+        assert_eq!(
+            board.status(),
+            GameState::Draw(DrawReason::InsufficientMaterial)
+        );
+    }
+
+    fn fen_error_field(fen: &str) -> usize {
+        match Board::new(fen.to_string()) {
+            Err(BoardError::FenError { field, .. }) => field,
+            Err(e) => panic!("{}: unexpected error {:?}", fen, e),
+            Ok(_) => panic!("{}: accepted invalid fen", fen),
+        }
+    }
+
+    #[test]
+    fn fen_rejects_bad_syntax() {
+        let cases = [
+            // missing fields
+            ("", 1),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 2),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", 5),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", 6),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 x",
+                7,
+            ),
+            // piece placement
+            ("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 1),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+                1,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/7/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4x3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/44/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/9/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            // other fields
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+                2,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+                3,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKkq - 0 1",
+                3,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+                4,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1",
+                4,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+                5,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1",
+                5,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 x",
+                6,
+            ),
+        ];
+        for (fen, field) in cases {
+            assert_eq!(fen_error_field(fen), field, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn fen_rejects_impossible_positions() {
+        let cases = [
+            // no kings, two kings
+            ("8/8/8/8/8/8/8/8 w - - 0 1", 1),
+            ("4k3/8/8/8/8/8/8/8 w - - 0 1", 1),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", 1),
+            // nine pawns
+            ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1", 1),
+            // more promoted pieces than missing pawns
+            ("4k3/8/8/8/8/Q1Q5/PPPPPPP1/Q3K3 w - - 0 1", 1),
+            // pawns on the back ranks
+            ("P3k3/8/8/8/8/8/8/4K3 w - - 0 1", 1),
+            ("4k3/8/8/8/8/8/8/4K2p w - - 0 1", 1),
+            // side not to move is in check
+            ("4k3/8/8/8/8/8/8/r3K3 b - - 0 1", 2),
+            ("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1", 2),
+            // castling rights without king and rook in place
+            ("4k3/8/8/8/8/8/8/R3K3 w K - 0 1", 3),
+            ("4k3/8/8/8/8/8/8/R2K3R w Q - 0 1", 3),
+            ("4k2r/8/8/8/8/8/8/4K3 w q - 0 1", 3),
+            // en passant target without a pawn that just double pushed
+            ("4k3/8/8/8/8/8/8/4K3 b - e3 0 1", 4),
+            ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", 4),
+            ("4k3/8/8/8/4P3/4N3/8/4K3 b - e3 0 1", 4),
+            ("4k3/8/8/8/4P3/8/8/4K3 b - e3 3 1", 5),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", 6),
+        ];
+        for (fen, field) in cases {
+            assert_eq!(fen_error_field(fen), field, "{}", fen);
+        }
+        // the valid version of the en passant case
+        assert!(Board::new("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1".to_string()).is_ok());
+    }
+
+    #[test]
+    fn fen_lenient() {
+        // analysis setups: no kings, extra pawns, missing move counters
+        for fen in [
+            "8/8/8/8/8/8/8/8 w - -",
+            "4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1",
+            "4k3/4R3/8/8/8/8/8/4K3 w - -",
+            "8/8/8/3q4/8/8/8/8 b - - 12 30",
+        ] {
+            let board = Board::new_lenient(fen.to_string()).unwrap();
+            assert_eq!(board.export_fen().unwrap().split(' ').count(), 6);
+        }
+        // syntax errors are still reported
+        assert!(matches!(
+            Board::new_lenient("8/8/8/8/8/8/8 w - -".to_string()),
+            Err(BoardError::FenError { field: 1, .. })
+        ));
+        assert!(matches!(
+            Board::new_lenient("8/8/8/8/8/8/8/8 w".to_string()),
+            Err(BoardError::FenError { field: 3, .. })
+        ));
+        // positions without a king can still generate moves
+        let board = Board::new_lenient("8/8/8/3q4/8/8/8/8 b - -".to_string()).unwrap();
+        assert_eq!(board.legal_moves().len(), 27);
+    }
+
+    #[test]
+    fn epd_parse_and_export() {
+        let line =
+            r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+        let epd = Epd::from_str(line).unwrap();
+        assert_eq!(
+            epd.board.export_fen().unwrap(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(epd.operands("id"), Some(&["WAC.001".to_string()][..]));
+        assert_eq!(
+            epd.moves("bm").unwrap(),
+            vec![Move::from_str("g3g6").unwrap()]
+        );
+        assert_eq!(epd.to_string(), line);
+
+        // move counters, numeric operands and strings with spaces and ';'
+        let line = r#"4k3/8/8/8/8/8/4P3/4K3 b - - hmvc 3; fmvn 40; acd 12; ce -35; c0 "quiet; move"; noop;"#;
+        let epd = Epd::from_str(line).unwrap();
+        assert_eq!(
+            epd.board.export_fen().unwrap(),
+            "4k3/8/8/8/8/8/4P3/4K3 b - - 3 40"
+        );
+        assert_eq!(epd.operands("ce"), Some(&["-35".to_string()][..]));
+        assert_eq!(epd.operands("noop"), Some(&[][..]));
+        assert_eq!(epd.operands("bm"), None);
+        assert_eq!(epd.to_string(), line);
+    }
+
+    #[test]
+    fn epd_check_move() {
+        let epd = Epd::from_str(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am Qe2;",
+        )
+        .unwrap();
+        let check = |mv: &str| epd.check_move(&epd.board.parse_move(mv).unwrap()).unwrap();
+        assert_eq!(check("Bb5"), Some(true));
+        assert_eq!(check("f1c4"), Some(true));
+        assert_eq!(check("d4"), Some(false));
+        assert_eq!(check("Qe2"), Some(false));
+
+        let epd = Epd::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - am e4;").unwrap();
+        let check = |mv: &str| epd.check_move(&epd.board.parse_move(mv).unwrap()).unwrap();
+        assert_eq!(check("e3"), Some(true));
+        assert_eq!(check("e4"), Some(false));
+
+        let epd = Epd::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - id \"x\";").unwrap();
+        assert_eq!(epd.check_move(&Move::from_str("e2e4").unwrap()), Ok(None));
+        // moves that aren't legal in the position
+        let epd = Epd::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - bm Nf3;").unwrap();
+        assert!(epd.check_move(&Move::from_str("e2e4").unwrap()).is_err());
+    }
+
+    #[test]
+    fn epd_errors() {
+        let epd = |s: &str| Epd::from_str(s).err();
+        assert!(matches!(
+            epd("4k3/8/8/8/8/8/4P3/4K3 w -"),
+            Some(BoardError::FenError { field: 4, .. })
+        ));
+        assert!(matches!(
+            epd("4k3/8/8/8/8/8/4P3/4K9 w - - bm e4;"),
+            Some(BoardError::FenError { field: 1, .. })
+        ));
+        assert!(matches!(
+            epd("4k3/8/8/8/8/8/4P3/4K3 w - - hmvc x;"),
+            Some(BoardError::FenError { field: 5, .. })
+        ));
+        for line in [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - bm e4",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - id \"x;",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - bm e4;;",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 1x e4;",
+        ] {
+            assert!(
+                matches!(epd(line), Some(BoardError::EpdError(_))),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut board = Board::startpos();
+        assert_eq!(board.undo(), Err(BoardError::NothingToUndo));
+        for mv in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+            play(&mut board, mv).unwrap();
+        }
+        let fen = board.export_fen().unwrap();
+        assert_eq!(board.undo(), Ok(Move::from_str("d8d5").unwrap()));
+        assert_eq!(piece_at(&board, "d5"), Some('P'));
+        assert_eq!(piece_at(&board, "d8"), Some('q'));
+        assert_eq!(board.redo(), Ok(Move::from_str("d8d5").unwrap()));
+        assert_eq!(board.export_fen().unwrap(), fen);
+        assert_eq!(board.redo(), Err(BoardError::NothingToRedo));
+        // a new move replaces the moves that were taken back
+        board.undo().unwrap();
+        play(&mut board, "c7c6").unwrap();
+        assert_eq!(board.redo(), Err(BoardError::NothingToRedo));
+
+        // the game carries on after taking back checkmate
+        let mut board = Board::startpos();
+        for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            play(&mut board, mv).unwrap();
+        }
+        assert_eq!(board.status(), GameState::BlackWin);
+        board.undo().unwrap();
+        assert_eq!(board.status(), GameState::Active);
+        assert_eq!(board.legal_moves().len(), 30);
+
+        // but resigning, running out of time or agreeing a draw can't be taken back
+        let mut board = Board::startpos();
+        play(&mut board, "e2e4").unwrap();
+        board.resign().unwrap();
+        assert_eq!(board.undo(), Err(BoardError::GameOver));
+        assert_eq!(board.status(), GameState::Resignation(Colour::Black));
+        assert_eq!(piece_at(&board, "e4"), Some('P'));
+        board = Board::startpos();
+        board.time_out().unwrap();
+        assert_eq!(board.undo(), Err(BoardError::GameOver));
+        board = Board::startpos();
+        board.agree_draw().unwrap();
+        assert_eq!(board.undo(), Err(BoardError::GameOver));
+
+        // undone positions no longer count towards repetition
+        let mut board = Board::startpos();
+        for mv in [
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ] {
+            play(&mut board, mv).unwrap();
+        }
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        board.undo().unwrap();
+        board.undo().unwrap();
+        assert_eq!(board.claimable_draw(), None);
+    }
+
+    #[test]
+    fn make_and_unmake_every_move() {
+        // castling both ways, en passant, promotions with and without capture
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+            "n1n1k3/1P6/8/8/8/8/6p1/4K2R b K - 0 40",
+        ] {
+            let mut board = Board::new(fen.to_string()).unwrap();
+            let moves = board.generate_legal_moves();
+            assert!(!moves.is_empty());
+            for (mv, move_type) in moves {
+                let undo = board.make_move(&mv, move_type);
+                assert_ne!(board.export_fen().unwrap(), fen);
+                board.unmake_move(undo);
+                assert_eq!(board.export_fen().unwrap(), fen, "{}", mv);
+            }
+        }
+    }
+
+    // xorshift64, a small deterministic random number generator for randomised tests
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn undo_restores_random_games() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            for _ in 0..20 {
+                // play random legal moves, remembering every position
+                let mut board = Board::new(fen.to_string()).unwrap();
+                let mut fens = vec![board.export_fen().unwrap()];
+                for _ in 0..rng.below(100) {
+                    let moves = board.legal_moves();
+                    if board.status() != GameState::Active {
+                        break;
+                    }
+                    board.process_move(&moves[rng.below(moves.len())]).unwrap();
+                    fens.push(board.export_fen().unwrap());
+                }
+                // every position comes back on the way to the start
+                for expected in fens.iter().rev().skip(1) {
+                    board.undo().unwrap();
+                    assert_eq!(&board.export_fen().unwrap(), expected);
+                }
+                assert_eq!(board.undo(), Err(BoardError::NothingToUndo));
+                assert_eq!(board.status(), GameState::Active);
+                // and again on the way back
+                for expected in fens.iter().skip(1) {
+                    board.redo().unwrap();
+                    assert_eq!(&board.export_fen().unwrap(), expected);
+                }
+                assert_eq!(board.redo(), Err(BoardError::NothingToRedo));
+            }
+        }
+    }
+
+    // the bitboards hold exactly the pieces on squares
+    fn assert_bitboards_in_step(board: &Board) {
+        let (mut pieces, mut colours) = ([0; 6], [0; 2]);
+        for (i, square) in board.squares.iter().enumerate() {
+            if let Some(piece) = square {
+                pieces[piece.piece_type as usize] |= 1 << i;
+                colours[piece.colour as usize] |= 1 << i;
+            }
+        }
+        assert_eq!(board.pieces, pieces, "{}", board.export_fen().unwrap());
+        assert_eq!(board.colours, colours, "{}", board.export_fen().unwrap());
+    }
+
+    #[test]
+    fn bitboard_moves_match_mailbox() {
+        let sorted = |moves: Vec<(Move, MoveType)>| {
+            let mut moves: Vec<String> = moves
+                .iter()
+                .map(|(mv, move_type)| format!("{} {:?}", mv, move_type))
+                .collect();
+            moves.sort();
+            moves
+        };
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            for _ in 0..10 {
+                let mut board = Board::new(fen.to_string()).unwrap();
+                while board.status() == GameState::Active && board.history.len() < 80 {
+                    assert_bitboards_in_step(&board);
+                    assert_eq!(
+                        sorted(board.pseudo_legal_moves()),
+                        sorted(board.generate_mailbox_moves()),
+                        "{}",
+                        board.export_fen().unwrap()
+                    );
+                    for colour in [Colour::White, Colour::Black] {
+                        assert_eq!(
+                            board.king_attacked(colour),
+                            move_logic::in_check(&colour, &board.squares)
+                        );
+                    }
+                    let moves = board.legal_moves();
+                    board.process_move(&moves[rng.below(moves.len())]).unwrap();
+                }
+                while board.undo().is_ok() {
+                    assert_bitboards_in_step(&board);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hash_updates_incrementally() {
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        let mut board = Board::new(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        )
+        .unwrap();
+        let start = board.hash();
+        while board.status() == GameState::Active && board.history.len() < 200 {
+            let moves = board.legal_moves();
+            board.process_move(&moves[rng.below(moves.len())]).unwrap();
+            assert_eq!(
+                board.hash(),
+                board.zobrist_hash(),
+                "{}",
+                board.export_fen().unwrap()
+            );
+        }
+        while board.undo().is_ok() {
+            assert_eq!(board.hash(), board.zobrist_hash());
+        }
+        assert_eq!(board.hash(), start);
+    }
+
+    #[test]
+    fn hash_identifies_positions() {
+        let hash = |fen: &str| Board::new(fen.to_string()).unwrap().hash();
+        // the same position reached by different move orders
+        let mut a = Board::startpos();
+        let mut b = Board::startpos();
+        for mv in ["g1f3", "g8f6", "b1c3"] {
+            play(&mut a, mv).unwrap();
+        }
+        for mv in ["b1c3", "g8f6", "g1f3"] {
+            play(&mut b, mv).unwrap();
+        }
+        assert_eq!(a.hash(), b.hash());
+        assert_ne!(a.hash(), Board::startpos().hash());
+        // side to move and castling rights count, move counters don't
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_ne!(hash(fen), hash("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"));
+        assert_ne!(hash(fen), hash("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1"));
+        assert_eq!(hash(fen), hash("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 12 40"));
+        // the en passant file only counts when the capture is possible
+        assert_ne!(
+            hash("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1"),
+            hash("4k3/8/8/3Pp3/8/8/8/4K3 w - - 0 1")
+        );
+        assert_eq!(
+            hash("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1"),
+            hash("4k3/8/8/4p3/8/8/8/4K3 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn attackers_and_captures() {
+        let board = Board::new("4k3/8/3p4/4n3/2B5/5N2/8/R3K3 w Q - 0 1".to_string()).unwrap();
+        let e5: usize = "e5".parse::<Coordinate>().unwrap().into();
+        let attackers: Vec<String> = bitboard::squares(board.attackers_to(e5, board.occupied()))
+            .map(|i| Coordinate::try_from(i).unwrap().to_string())
+            .collect();
+        assert_eq!(attackers, vec!["f3", "d6"]);
+        let mut board = board;
+        let captures: Vec<String> = board
+            .generate_legal_captures()
+            .iter()
+            .map(|(mv, _)| mv.to_string())
+            .collect();
+        assert_eq!(captures, vec!["f3e5"]);
+    }
+
+    #[test]
+    fn null_move() {
+        let mut board = Board::new("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1".to_string()).unwrap();
+        let (fen, hash) = (board.export_fen().unwrap(), board.hash());
+        let undo = board.make_null_move();
+        assert_eq!(board.active_colour(), Colour::Black);
+        assert_eq!(board.hash(), board.zobrist_hash());
+        assert!(board
+            .export_fen()
+            .unwrap()
+            .starts_with("4k3/8/8/3Pp3/8/8/8/4K3 b - -"));
+        board.unmake_null_move(undo);
+        assert_eq!((board.export_fen().unwrap(), board.hash()), (fen, hash));
+    }
 }
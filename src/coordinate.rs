@@ -125,6 +125,8 @@ mod tests {
         assert_eq!(coord, Coordinate::try_from(index).unwrap());
     }
     #[test]
+    // the conversion can't fail, the test is older than clippy's lint for that
+    #[allow(clippy::unnecessary_fallible_conversions)]
     fn coord_into_usize() {
         let coord = Coordinate { file: 'a', rank: 1 };
         let index = 0usize;
@@ -0,0 +1,880 @@
+use std::{
+    cmp::Reverse,
+    hash::{BuildHasher, RandomState},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::Board,
+    errors::BoardError,
+    evaluation::evaluate,
+    move_logic::{Move, MoveType},
+    ordering::{order_captures, see, MoveOrdering},
+    pieces::{Colour, PieceType},
+    transposition::{Bound, Entry, TableStats, TranspositionTable},
+};
+
+// scores are in centipawns for the side to move. A mate is scored MATE less the number of plies
+// it takes, so the engine prefers quicker mates and slower losses
+pub const MATE: i32 = 30_000;
+// scores further from zero than this are forced mates
+pub const MATE_THRESHOLD: i32 = MATE - 1_000;
+const INFINITY: i32 = 32_000;
+// deepest iteration when no depth limit is given
+pub const MAX_DEPTH: usize = 64;
+// kept back from the clock for the time it takes to send the move
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+// a game is assumed to last this many more moves when dividing up the clock
+const MOVES_TO_GO: u32 = 30;
+// however large the increment, a move doesn't plan on more than a quarter of the clock and is cut
+// off at half of it
+const SOFT_CLOCK_SHARE: u32 = 4;
+const HARD_CLOCK_SHARE: u32 = 2;
+// null move pruning searches this many plies less, and only with at least NULL_MOVE_DEPTH left
+const NULL_MOVE_REDUCTION: usize = 2;
+const NULL_MOVE_DEPTH: usize = 3;
+// full strength, lower skill levels search less deep and sometimes pick a weaker move
+pub const MAX_SKILL_LEVEL: usize = 20;
+// how much worse than the best a move may score to be picked, for each level below the top
+const SKILL_MARGIN: i32 = 10;
+// moves a weakened engine picks from
+const SKILL_LINES: usize = 4;
+
+// when to stop searching, limits that aren't set don't apply
+// with no limits at all the search only ends at MAX_DEPTH or when stopped from outside
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    // time left on each side's clock, and what is added after each of their moves
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    // moves to play before the clock is topped up, otherwise the time has to last the game
+    pub movestogo: Option<u32>,
+    // the only moves searched from the root, every legal move when empty
+    pub searchmoves: Vec<Move>,
+}
+
+impl Limits {
+    pub fn depth(depth: usize) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    // how long colour may think as (soft, hard) limits: no new iteration is started after the
+    // soft one, the search is cut off at the hard one
+    fn time_budget(&self, colour: Colour) -> Option<(Duration, Duration)> {
+        if let Some(movetime) = self.movetime {
+            return Some((movetime, movetime));
+        }
+        let (time, increment) = match colour {
+            Colour::White => (self.wtime?, self.winc.unwrap_or_default()),
+            Colour::Black => (self.btime?, self.binc.unwrap_or_default()),
+        };
+        let available = time.saturating_sub(MOVE_OVERHEAD);
+        let moves = self.movestogo.unwrap_or(MOVES_TO_GO).max(1);
+        let soft = (available / moves + increment * 3 / 4).min(available / SOFT_CLOCK_SHARE);
+        Some((soft, (soft * 4).min(available / HARD_CLOCK_SHARE)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    // plies searched in the last completed iteration
+    pub depth: usize,
+    // positions visited
+    pub nodes: u64,
+    pub time: Duration,
+    // the moves both sides are expected to play, starting with best_move
+    pub pv: Vec<Move>,
+    // 1 for the best line, 2 for the next best and so on, see Engine::set_multi_pv()
+    pub multipv: usize,
+}
+
+// moves to mate, negative when the side to move is getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    (score.abs() >= MATE_THRESHOLD).then(|| (MATE - score.abs() + 1) / 2 * score.signum())
+}
+
+// "+0.35" in pawns, or "#3" and "#-3" for mates in moves for and against the side to move
+pub fn format_score(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) => format!("#{}", moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+// negamax alpha-beta search: every position is scored for the side to move, so a move is worth
+// minus the score of the position it leads to
+// searches deepen one ply at a time until a limit is reached, each iteration starting with the
+// best move of the one before. Results are kept in a transposition table between iterations and
+// searches, for move ordering and to skip positions already searched deep enough
+// at the end of the depth a quiescence search plays out captures, so a position isn't scored in
+// the middle of an exchange. Checks are searched a ply deeper, and positions good enough to give
+// the opponent a free move are cut off early
+#[derive(Default)]
+pub struct Engine {
+    table: TranspositionTable,
+    ordering: MoveOrdering,
+    nodes: u64,
+    // hashes of the game's positions, followed by the positions on the line being searched
+    line: Vec<u64>,
+    // where repetitions are looked for in line: positions before a null move can't be repeated
+    // after it, passing isn't a move that could have been played
+    repetition_start: usize,
+    // set from another thread to end the search early
+    stop: Arc<AtomicBool>,
+    // the iteration under way and the limits it has to keep to
+    iteration: usize,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    // out of budget, the unfinished iteration is thrown away
+    stopped: bool,
+    // best moves reported on, 0 counts as 1
+    multi_pv: usize,
+    // full strength when None
+    skill_level: Option<usize>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    // a new, empty table of the given size
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.table = TranspositionTable::new(megabytes);
+    }
+
+    // forget earlier searches, for a new game
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    // how the transposition table did in the last search
+    pub fn table_stats(&self) -> TableStats {
+        self.table.stats()
+    }
+
+    // permille of the transposition table in use
+    pub fn hashfull(&self) -> usize {
+        self.table.hashfull()
+    }
+
+    // the best `lines` moves are searched exactly and each reported on, instead of only the best
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines;
+    }
+
+    // from 0 to MAX_SKILL_LEVEL, which plays as well as the engine can
+    pub fn set_skill_level(&mut self, level: usize) {
+        self.skill_level = (level < MAX_SKILL_LEVEL).then_some(level);
+    }
+
+    // storing true stops the search under way, which then returns its best move so far. The
+    // engine never clears it, so a stop sent just before a search starts isn't lost: a search
+    // started with it set only completes its first iteration
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // the best move for the side to move within the limits
+    pub fn search(&mut self, board: &Board, limits: &Limits) -> Result<SearchResult, BoardError> {
+        self.search_reporting(board, limits, |_| {})
+    }
+
+    // search, calling report with the result of every completed iteration, once for each line
+    // with multi_pv set
+    pub fn search_reporting(
+        &mut self,
+        board: &Board,
+        limits: &Limits,
+        mut report: impl FnMut(&SearchResult),
+    ) -> Result<SearchResult, BoardError> {
+        let start = Instant::now();
+        let mut board = board.clone();
+        // a game drawn by rule still has moves to play, which a GUI may ask for
+        let mut moves = board.generate_legal_moves();
+        if moves.is_empty() {
+            return Err(BoardError::GameOver);
+        }
+        if !limits.searchmoves.is_empty() {
+            moves.retain(|(mv, _)| limits.searchmoves.contains(mv));
+            if moves.is_empty() {
+                return Err(BoardError::InvalidMove);
+            }
+        }
+        let budget = limits.time_budget(board.active_colour());
+        self.nodes = 0;
+        self.line = board.position_hashes().to_vec();
+        self.repetition_start = 0;
+        self.stopped = false;
+        self.node_limit = limits.nodes;
+        self.deadline = budget.map(|(_, hard)| start + hard);
+        self.table.new_search();
+        self.ordering.clear();
+
+        let hash_move = self
+            .table
+            .probe(board.hash())
+            .and_then(|entry| entry.best_move);
+        self.ordering.order(&board, &mut moves, hash_move, 0);
+        let reported = self.multi_pv.max(1);
+        let (lines, max_depth) = match self.skill_level {
+            Some(level) => (reported.max(SKILL_LINES), level / 2 + 1),
+            None => (reported, MAX_DEPTH),
+        };
+        let lines = lines.min(moves.len());
+        let mut results = vec![];
+        for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(max_depth).max(1) {
+            self.iteration = depth;
+            let scores = self.search_root(&mut board, &moves, depth, lines);
+            if self.stopped {
+                break;
+            }
+            // search the best moves first next time, their scores make the others quicker to
+            // refute. Those that failed low only have a bound, so keep them in their old order
+            let mut ranked: Vec<usize> = (0..moves.len()).collect();
+            ranked.sort_by_key(|i| Reverse(scores[*i]));
+            ranked.truncate(lines);
+            let mut reordered: Vec<(Move, MoveType)> = ranked.iter().map(|i| moves[*i]).collect();
+            reordered.extend(
+                (0..moves.len())
+                    .filter(|i| !ranked.contains(i))
+                    .map(|i| moves[i]),
+            );
+            moves = reordered;
+            let score = scores[ranked[0]];
+            self.table.store(Entry::new(
+                board.hash(),
+                Some(moves[0].0),
+                score,
+                depth,
+                Bound::Exact,
+            ));
+            results = ranked
+                .iter()
+                .enumerate()
+                .map(|(n, i)| SearchResult {
+                    best_move: moves[n].0,
+                    score: scores[*i],
+                    depth,
+                    nodes: self.nodes,
+                    time: start.elapsed(),
+                    pv: self.principal_variation(&mut board, moves[n]),
+                    multipv: n + 1,
+                })
+                .collect();
+            results.iter().take(reported).for_each(&mut report);
+            // a mate within the depth searched can't be improved on
+            if MATE - score.abs() <= depth as i32 {
+                break;
+            }
+            if budget.is_some_and(|(soft, _)| start.elapsed() >= soft) {
+                break;
+            }
+        }
+        // the first iteration always completes, see out_of_budget()
+        let mut result = results.swap_remove(self.pick(&results));
+        result.nodes = self.nodes;
+        result.time = start.elapsed();
+        Ok(result)
+    }
+
+    // index of the line to play: the best one, unless the skill level is lowered, then any
+    // that isn't too much worse
+    fn pick(&self, results: &[SearchResult]) -> usize {
+        let Some(level) = self.skill_level else {
+            return 0;
+        };
+        let margin = (MAX_SKILL_LEVEL - level) as i32 * SKILL_MARGIN;
+        let candidates = results
+            .iter()
+            .take_while(|result| result.score >= results[0].score - margin)
+            .count();
+        RandomState::new().hash_one(self.nodes) as usize % candidates
+    }
+
+    // the line starting with first, followed as far as the transposition table has best moves
+    // for it that are still legal
+    fn principal_variation(&self, board: &mut Board, first: (Move, MoveType)) -> Vec<Move> {
+        let mut pv = vec![first.0];
+        let mut undos = vec![board.make_move(&first.0, first.1)];
+        let mut seen = vec![board.hash()];
+        while pv.len() < MAX_DEPTH {
+            let Some(best) = self
+                .table
+                .peek(board.hash())
+                .and_then(|entry| entry.best_move)
+            else {
+                break;
+            };
+            let Some((mv, move_type)) = board
+                .generate_legal_moves()
+                .into_iter()
+                .find(|(mv, _)| *mv == best)
+            else {
+                break;
+            };
+            pv.push(mv);
+            undos.push(board.make_move(&mv, move_type));
+            // a repetition would go round in circles
+            if seen.contains(&board.hash()) {
+                break;
+            }
+            seen.push(board.hash());
+        }
+        for undo in undos.into_iter().rev() {
+            board.unmake_move(undo);
+        }
+        pv
+    }
+
+    // a score for every move, exact for the best `lines` of them and an upper bound for the rest
+    fn search_root(
+        &mut self,
+        board: &mut Board,
+        moves: &[(Move, MoveType)],
+        depth: usize,
+        lines: usize,
+    ) -> Vec<i32> {
+        let mut scores = Vec::with_capacity(moves.len());
+        // a move has to beat the worst of the best lines so far to become one of them
+        let mut alpha = -INFINITY;
+        for (mv, move_type) in moves {
+            let undo = board.make_move(mv, *move_type);
+            self.line.push(board.hash());
+            let score = -self.negamax(board, depth - 1, 1, -INFINITY, -alpha, true);
+            self.line.pop();
+            board.unmake_move(undo);
+            if self.stopped {
+                break;
+            }
+            scores.push(score);
+            if scores.len() >= lines {
+                let mut best = scores.clone();
+                best.sort_unstable_by_key(|score| Reverse(*score));
+                alpha = best[lines - 1];
+            }
+        }
+        scores
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: usize,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        null_move_allowed: bool,
+    ) -> i32 {
+        let in_check = board.in_check();
+        if depth == 0 && !in_check {
+            return self.quiesce(board, ply, alpha, beta);
+        }
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        if self.is_draw(board) {
+            return 0;
+        }
+        // a check is searched a ply deeper, so a forcing line doesn't end at the horizon with
+        // the reply still to come
+        let depth = depth + in_check as usize;
+        let hash = board.hash();
+        let entry = self.table.probe(hash);
+        if let Some(entry) = entry.filter(|entry| entry.depth as usize >= depth) {
+            let score = score_from_table(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                self.table.record_cutoff();
+                return score;
+            }
+        }
+
+        // if passing still leaves the opponent below beta, a real move would too. Not when
+        // in check, nor with only pawns left where passing can be the best move there is
+        if null_move_allowed
+            && !in_check
+            && depth >= NULL_MOVE_DEPTH
+            && beta.abs() < MATE_THRESHOLD
+            && has_pieces(board)
+            && evaluate(board) >= beta
+        {
+            let undo = board.make_null_move();
+            self.line.push(board.hash());
+            let start = std::mem::replace(&mut self.repetition_start, self.line.len() - 1);
+            let score = -self.negamax(
+                board,
+                depth - 1 - NULL_MOVE_REDUCTION,
+                ply + 1,
+                -beta,
+                -beta + 1,
+                false,
+            );
+            self.repetition_start = start;
+            self.line.pop();
+            board.unmake_null_move(undo);
+            if self.stopped {
+                return 0;
+            }
+            // a mate found after passing isn't proven
+            if score >= beta {
+                return beta;
+            }
+        }
+
+        let mut moves = board.generate_legal_moves();
+        if moves.is_empty() {
+            // checkmate or stalemate
+            return match in_check {
+                true => ply - MATE,
+                false => 0,
+            };
+        }
+        let hash_move = entry.and_then(|entry| entry.best_move);
+        self.ordering
+            .order(board, &mut moves, hash_move, ply as usize);
+
+        let original_alpha = alpha;
+        let mut best = (-INFINITY, None);
+        for (mv, move_type) in moves {
+            let undo = board.make_move(&mv, move_type);
+            self.line.push(board.hash());
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, true);
+            self.line.pop();
+            board.unmake_move(undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best.0 {
+                best = (score, Some(mv));
+            }
+            alpha = alpha.max(score);
+            // the opponent has a better option earlier on and won't allow this position
+            if alpha >= beta {
+                self.ordering
+                    .record_cutoff(board, mv, move_type, depth, ply as usize);
+                break;
+            }
+        }
+        let bound = if best.0 >= beta {
+            Bound::Lower
+        } else if best.0 <= original_alpha {
+            // every move failed low, none of them is known to be best
+            best.1 = None;
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.table.store(Entry::new(
+            hash,
+            best.1,
+            score_to_table(best.0, ply),
+            depth,
+            bound,
+        ));
+        best.0
+    }
+
+    // search captures and promotions until the position is quiet. The side to move can stand
+    // pat, keeping the static evaluation rather than making a capture, except when in check
+    // where every move is searched
+    fn quiesce(&mut self, board: &mut Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        if self.is_draw(board) {
+            return 0;
+        }
+        let in_check = board.in_check();
+        let (mut best, mut moves) = match in_check {
+            true => {
+                let moves = board.generate_legal_moves();
+                if moves.is_empty() {
+                    return ply - MATE;
+                }
+                (-INFINITY, moves)
+            }
+            false => {
+                let stand_pat = evaluate(board);
+                if stand_pat >= beta {
+                    return stand_pat;
+                }
+                alpha = alpha.max(stand_pat);
+                let mut moves = board.generate_legal_captures();
+                // captures that lose material and promotions to anything but a queen won't
+                // improve on standing pat
+                moves.retain(|(mv, move_type)| match mv.promotion {
+                    Some(piece_type) => piece_type == PieceType::Queen,
+                    None => see(board, mv, *move_type) >= 0,
+                });
+                (stand_pat, moves)
+            }
+        };
+        order_captures(board, &mut moves);
+
+        for (mv, move_type) in moves {
+            let undo = board.make_move(&mv, move_type);
+            self.line.push(board.hash());
+            let score = -self.quiesce(board, ply + 1, -beta, -alpha);
+            self.line.pop();
+            board.unmake_move(undo);
+            if self.stopped {
+                return 0;
+            }
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // checked at every node, though the clock is only read every so often
+    // the first iteration is quick and always completes, so there is a move to return
+    fn out_of_budget(&mut self) -> bool {
+        if !self.stopped && self.iteration > 1 {
+            self.stopped = self.stop.load(Ordering::Relaxed)
+                || self.node_limit.is_some_and(|limit| self.nodes >= limit)
+                || (self.nodes.is_multiple_of(1024)
+                    && self
+                        .deadline
+                        .is_some_and(|deadline| Instant::now() >= deadline));
+        }
+        self.stopped
+    }
+
+    // fifty moves without a capture or pawn move, or a position seen before in the game or on
+    // this line. One repetition is enough, the side that can avoid the draw will do so
+    fn is_draw(&self, board: &mut Board) -> bool {
+        // unless the move that made it fifty was mate, which comes first
+        if board.half_move_clock() >= 100 {
+            return !board.in_check() || !board.generate_legal_moves().is_empty();
+        }
+        let hash = board.hash();
+        // positions from before the last capture or pawn move can't come back
+        self.line[self.repetition_start..self.line.len() - 1]
+            .iter()
+            .rev()
+            .take(board.half_move_clock())
+            .any(|h| *h == hash)
+    }
+}
+
+// pieces besides pawns and the king for the side to move. Without them zugzwang is common, and
+// passing would look better than any move there is
+fn has_pieces(board: &Board) -> bool {
+    let colour = board.active_colour();
+    [
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]
+    .iter()
+    .any(|piece_type| board.pieces(colour, *piece_type) != 0)
+}
+
+// mate scores count plies from the root, in the table they count from the position itself so
+// they stay right when it is reached at another ply
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s >= MATE_THRESHOLD => s + ply,
+        s if s <= -MATE_THRESHOLD => s - ply,
+        s => s,
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s >= MATE_THRESHOLD => s - ply,
+        s if s <= -MATE_THRESHOLD => s + ply,
+        s => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, depth: usize) -> (String, SearchResult) {
+        let board = Board::new(fen.to_string()).unwrap();
+        let result = Engine::new().search(&board, &Limits::depth(depth)).unwrap();
+        (board.to_san(&result.best_move), result)
+    }
+
+    #[test]
+    fn finds_mate() {
+        // back rank mate in one
+        let (san, result) = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(san, "Ra8#");
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(format_score(result.score), "#1");
+        // mate in two, forcing the defending rook away first
+        let (san, result) = search("2r3k1/5ppp/8/8/8/8/4R3/4R1K1 w - - 0 1", 3);
+        assert_eq!(san, "Re8+");
+        assert_eq!(format_score(result.score), "#2");
+    }
+
+    #[test]
+    fn mate_beats_the_fifty_move_rule() {
+        // Ra8# is the hundredth half-move without a capture or pawn move
+        let (san, result) = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80", 2);
+        assert_eq!(san, "Ra8#");
+        assert_eq!(result.score, MATE - 1);
+        // with black to move, a king move makes it a draw before Ra8 can be played
+        let (_, result) = search("6k1/5ppp/8/8/8/8/8/R5K1 b - - 99 80", 2);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn wins_material() {
+        let (san, result) = search("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 3);
+        assert_eq!(san, "Rxd5");
+        assert!(result.score > 300);
+        // and doesn't take a defended pawn with the queen
+        let (san, _) = search("4k3/2p5/3p4/8/8/8/3Q4/4K3 w - - 0 1", 2);
+        assert_ne!(san, "Qxd6");
+    }
+
+    #[test]
+    fn quiescence_sees_recaptures() {
+        // at one ply Qxd6 wins a pawn, until cxd6 is played out
+        let (san, result) = search("4k3/2p5/3p4/8/8/8/3Q4/4K3 w - - 0 1", 1);
+        assert_ne!(san, "Qxd6");
+        assert!(result.score > 700);
+    }
+
+    #[test]
+    fn checks_are_extended() {
+        // the mate in two needs three plies, checks make up the difference
+        let (san, result) = search("2r3k1/5ppp/8/8/8/8/4R3/4R1K1 w - - 0 1", 2);
+        assert_eq!(san, "Re8+");
+        assert_eq!(format_score(result.score), "#2");
+    }
+
+    #[test]
+    fn avoids_stalemate() {
+        // Qb6 would leave black without a move
+        let (san, result) = search("k7/8/8/2Q5/8/8/8/K7 w - - 0 1", 2);
+        assert_ne!(san, "Qb6");
+        assert!(result.score > 500);
+    }
+
+    #[test]
+    fn null_moves_need_pieces() {
+        // Nd6 mates in three once black, with only pawns, runs out of safe moves. Passing there
+        // would hold, so black must never be given one
+        let (san, result) = search("3k4/5R1p/5K2/1N3p2/8/8/8/8 w - - 0 1", 5);
+        assert_eq!(san, "Nd6");
+        assert_eq!(format_score(result.score), "#3");
+    }
+
+    #[test]
+    fn null_moves_break_repetitions() {
+        let mut board = Board::new("r3k3/8/8/8/8/8/8/4K1N1 w - - 10 1".to_string()).unwrap();
+        let mut engine = Engine::new();
+        engine.line = board.position_hashes().to_vec();
+        board.make_null_move();
+        engine.line.push(board.hash());
+        engine.repetition_start = engine.line.len() - 1;
+        // back to the first position, with white to move, only because white passed
+        for uci in ["a8a7", "g1f3", "a7a6", "f3g1", "a6a8"] {
+            let (mv, move_type) = board
+                .generate_legal_moves()
+                .into_iter()
+                .find(|(mv, _)| mv.to_string() == uci)
+                .unwrap();
+            board.make_move(&mv, move_type);
+            engine.line.push(board.hash());
+        }
+        assert_eq!(board.hash(), engine.line[0]);
+        assert!(!engine.is_draw(&mut board));
+        engine.repetition_start = 0;
+        assert!(engine.is_draw(&mut board));
+    }
+
+    #[test]
+    fn finished_games_have_no_move() {
+        let board = Board::new("7k/8/8/8/8/8/5PPP/r5K1 w - - 0 1".to_string()).unwrap();
+        assert_eq!(
+            Engine::new().search(&board, &Limits::depth(2)),
+            Err(BoardError::GameOver)
+        );
+    }
+
+    #[test]
+    fn iterations_deepen_until_a_limit() {
+        let board = Board::startpos();
+        let mut engine = Engine::new();
+        let mut depths = vec![];
+        let result = engine
+            .search_reporting(&board, &Limits::depth(3), |r| depths.push(r.depth))
+            .unwrap();
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+
+        // a node limit cuts the iteration short, the last completed one is returned
+        let limits = Limits {
+            nodes: Some(2_000),
+            ..Limits::default()
+        };
+        let result = engine.search(&board, &limits).unwrap();
+        assert!(result.depth < MAX_DEPTH);
+        assert!(result.nodes <= 2_000);
+
+        // mates end the search early
+        let board = Board::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1".to_string()).unwrap();
+        let result = engine.search(&board, &Limits::default()).unwrap();
+        assert_eq!(result.depth, 1);
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn multiple_lines() {
+        let board = Board::startpos();
+        let mut engine = Engine::new();
+        engine.set_multi_pv(3);
+        let mut lines = vec![];
+        let result = engine
+            .search_reporting(&board, &Limits::depth(3), |r| lines.push(r.clone()))
+            .unwrap();
+        assert_eq!(lines.len(), 9);
+        let last = &lines[6..];
+        assert_eq!(
+            last.iter().map(|r| r.multipv).collect::<Vec<usize>>(),
+            vec![1, 2, 3]
+        );
+        assert!(last[0].score >= last[1].score && last[1].score >= last[2].score);
+        assert_ne!(last[0].best_move, last[1].best_move);
+        assert_eq!(result.best_move, last[0].best_move);
+        // each line is followed out of the table, from the side to move's move on
+        assert_eq!(last[0].pv[0], last[0].best_move);
+        assert!(last[0].pv.len() >= 2);
+
+        // a weakened engine searches less deep, still playing a legal move
+        engine.set_multi_pv(1);
+        engine.set_skill_level(2);
+        let result = engine.search(&board, &Limits::depth(6)).unwrap();
+        assert_eq!(result.depth, 2);
+        assert!(board.legal_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn time_limits() {
+        let board = Board::startpos();
+        let mut engine = Engine::new();
+        let limits = Limits {
+            movetime: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let result = engine.search(&board, &limits).unwrap();
+        assert!(
+            result.time < Duration::from_millis(500),
+            "{:?}",
+            result.time
+        );
+
+        // the stop flag ends a search without limits
+        let stop = engine.stop_flag();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+        let result = engine.search(&board, &Limits::default()).unwrap();
+        stopper.join().unwrap();
+        assert!(result.depth < MAX_DEPTH);
+        assert!(board.legal_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn clock_budget() {
+        let limits = Limits {
+            wtime: Some(Duration::from_secs(60)),
+            btime: Some(Duration::from_secs(1)),
+            winc: Some(Duration::from_secs(1)),
+            ..Limits::default()
+        };
+        let (soft, hard) = limits.time_budget(Colour::White).unwrap();
+        assert!(soft > Duration::from_secs(2) && soft < Duration::from_secs(3));
+        assert_eq!(hard, soft * 4);
+        // never more than what's left
+        let (soft, hard) = limits.time_budget(Colour::Black).unwrap();
+        assert!(soft < Duration::from_millis(40));
+        assert!(hard < Duration::from_secs(1));
+        // a large increment doesn't let one move use up the clock
+        let limits = Limits {
+            btime: Some(Duration::from_secs(1)),
+            binc: Some(Duration::from_secs(2)),
+            ..Limits::default()
+        };
+        let available = Duration::from_secs(1) - MOVE_OVERHEAD;
+        let (soft, hard) = limits.time_budget(Colour::Black).unwrap();
+        assert_eq!(soft, available / SOFT_CLOCK_SHARE);
+        assert_eq!(hard, available / HARD_CLOCK_SHARE);
+        assert_eq!(Limits::depth(3).time_budget(Colour::White), None);
+    }
+
+    #[test]
+    fn transposition_table_saves_work() {
+        let board = Board::new(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        )
+        .unwrap();
+        let mut engine = Engine::new();
+        let first = engine.search(&board, &Limits::depth(4)).unwrap();
+        let stats = engine.table_stats();
+        assert!(stats.hits > 0 && stats.cutoffs > 0 && stats.stores > 0);
+        assert!(engine.hashfull() > 0);
+        // the same search again is answered mostly from the table
+        let second = engine.search(&board, &Limits::depth(4)).unwrap();
+        assert!(second.nodes < first.nodes / 2);
+        assert_eq!(second.score, first.score);
+        assert!(engine.table_stats().hit_rate() > stats.hit_rate());
+        // without it the same move and score are found
+        engine.clear();
+        engine.set_hash_size(0);
+        let small = engine.search(&board, &Limits::depth(4)).unwrap();
+        assert_eq!(
+            (small.best_move, small.score),
+            (first.best_move, first.score)
+        );
+    }
+
+    #[test]
+    fn mate_scores_in_the_table() {
+        assert_eq!(score_from_table(score_to_table(MATE - 5, 3), 3), MATE - 5);
+        // a mate 2 plies from a position stored at ply 3, found again at ply 1
+        assert_eq!(score_from_table(score_to_table(MATE - 5, 3), 1), MATE - 3);
+        assert_eq!(score_to_table(-MATE + 4, 2), -MATE + 2);
+        assert_eq!(score_to_table(150, 7), 150);
+    }
+
+    #[test]
+    fn score_formats() {
+        assert_eq!(format_score(35), "+0.35");
+        assert_eq!(format_score(-120), "-1.20");
+        assert_eq!(format_score(MATE - 3), "#2");
+        assert_eq!(format_score(2 - MATE), "#-1");
+    }
+}
@@ -7,7 +7,11 @@ use std::{
 #[derive(Debug, PartialEq)]
 pub enum BoardError {
     //creation errors
-    FenError(String),
+    // fen field number (1 == piece placement ... 6 == full-move number) and what is wrong with it
+    FenError {
+        field: usize,
+        reason: String,
+    },
     PieceError,
     ParseInt(std::num::ParseIntError),
     ColourError,
@@ -21,8 +25,27 @@ pub enum BoardError {
     WrongColour,
     EmptySquare,
     InCheck,
-    PgnError,
-    UciError,
+    GameOver,
+    NoDrawToClaim,
+    NothingToUndo,
+    NothingToRedo,
+    // notation errors
+    SanError(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+    EpdError(String),
+    // position in the pgn text (1 based), the offending token and what is wrong with it
+    PgnError {
+        line: usize,
+        column: usize,
+        token: String,
+        reason: String,
+    },
+    // a uci command that can't be understood
+    UciError(String),
+    EngineError(String),
+    // reading or writing files
+    IoError(String),
 }
 
 impl From<ParseIntError> for BoardError {
@@ -33,7 +56,9 @@ impl From<ParseIntError> for BoardError {
 impl Display for BoardError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let error_msg: &str = match self {
-            Self::FenError(s) => s,
+            Self::FenError { field, reason } => {
+                &format!("invalid fen, field {}: {}", field, reason)
+            }
             Self::PieceError => "error creating piece",
             Self::ParseInt(e) => &format!("parse int error: {}", e),
             Self::ColourError => "error creating colour",
@@ -47,8 +72,26 @@ impl Display for BoardError {
             Self::WrongColour => "can't move opponents piece!",
             Self::EmptySquare => "start square is empty",
             Self::InCheck => "king must escape check!",
-            Self::PgnError => "error pgn",
-            Self::UciError => "error uci",
+            Self::GameOver => "the game is over, no more moves can be played",
+            Self::NoDrawToClaim => "no draw can be claimed in this position",
+            Self::NothingToUndo => "no moves to undo",
+            Self::NothingToRedo => "no moves to redo",
+            Self::SanError(s) => &format!("can't read move: {}", s),
+            Self::IllegalMove(s) => &format!("illegal move: {}", s),
+            Self::AmbiguousMove(s) => &format!("ambiguous move: {}", s),
+            Self::EpdError(s) => &format!("invalid epd: {}", s),
+            Self::PgnError {
+                line,
+                column,
+                token,
+                reason,
+            } => &format!(
+                "invalid pgn, line {} column {} at '{}': {}",
+                line, column, token, reason
+            ),
+            Self::UciError(s) => &format!("uci error: {}", s),
+            Self::EngineError(s) => &format!("engine error: {}", s),
+            Self::IoError(s) => &format!("file error: {}", s),
         };
         write!(f, "{}", error_msg)
     }
@@ -0,0 +1,204 @@
+use crate::{
+    bitboard,
+    board::Board,
+    pieces::{Colour, PieceType},
+};
+
+// static evaluation in centipawns: material plus piece-square tables, with separate values for
+// the middlegame and the endgame blended by how much material is left on the board
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// indexed by PieceType
+const MIDDLEGAME_VALUES: [i32; 6] = [82, 365, 337, 477, 1025, 0];
+const ENDGAME_VALUES: [i32; 6] = [94, 297, 281, 512, 936, 0];
+// knights and bishops count 1, rooks 2 and queens 4, 24 with all pieces on the board
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const FULL_PHASE: i32 = 24;
+
+// tables are laid out as the board is seen from white's side, a8 first
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+// passed or not, pawns are worth more the closer they get to promoting
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+// the king hides behind its pawns while there are pieces around to attack it
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+// and heads for the centre once they are gone
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// indexed by PieceType
+const MIDDLEGAME_TABLES: [&[i32; 64]; 6] = [
+    &PAWN_MIDDLEGAME,
+    &BISHOP,
+    &KNIGHT,
+    &ROOK,
+    &QUEEN,
+    &KING_MIDDLEGAME,
+];
+const ENDGAME_TABLES: [&[i32; 64]; 6] = [
+    &PAWN_ENDGAME,
+    &BISHOP,
+    &KNIGHT,
+    &ROOK,
+    &QUEEN,
+    &KING_ENDGAME,
+];
+
+// score of the position for the side to move, positive when they are better off
+pub fn evaluate(board: &Board) -> i32 {
+    let (mut middlegame, mut endgame, mut phase) = (0, 0, 0);
+    for colour in [Colour::White, Colour::Black] {
+        let sign = match colour {
+            Colour::White => 1,
+            Colour::Black => -1,
+        };
+        for piece_type in PIECE_TYPES {
+            let p = piece_type as usize;
+            for square in bitboard::squares(board.pieces(colour, piece_type)) {
+                // flip white's squares to match the table layout, black's read it mirrored
+                let i = match colour {
+                    Colour::White => square ^ 56,
+                    Colour::Black => square,
+                };
+                middlegame += sign * (MIDDLEGAME_VALUES[p] + MIDDLEGAME_TABLES[p][i]);
+                endgame += sign * (ENDGAME_VALUES[p] + ENDGAME_TABLES[p][i]);
+                phase += PHASE_WEIGHTS[p];
+            }
+        }
+    }
+    // early promotions can take the phase past the start
+    let phase = phase.min(FULL_PHASE);
+    let score = (middlegame * phase + endgame * (FULL_PHASE - phase)) / FULL_PHASE;
+    match board.active_colour() {
+        Colour::White => score,
+        Colour::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(fen: &str) -> i32 {
+        evaluate(&Board::new(fen.to_string()).unwrap())
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        assert_eq!(evaluate(&Board::startpos()), 0);
+        // colours swapped and the board turned upside down
+        assert_eq!(
+            eval("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 3"),
+            eval("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR w KQkq - 2 3")
+        );
+    }
+
+    #[test]
+    fn score_is_for_the_side_to_move() {
+        let white = eval("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1");
+        let black = eval("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1");
+        assert!(white > 800);
+        assert_eq!(white, -black);
+    }
+
+    #[test]
+    fn kings_centralise_in_the_endgame() {
+        // the same king move is judged differently with and without pieces on the board
+        let corner = eval("7k/8/8/8/8/8/8/K7 w - - 0 1");
+        let centre = eval("7k/8/8/8/3K4/8/8/8 w - - 0 1");
+        assert!(centre > corner);
+        let castled = eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1");
+        let centre = eval("rnbqkbnr/pppppppp/8/8/3K4/8/PPPPPPPP/RNBQ1R2 w kq - 0 1");
+        assert!(castled > centre);
+    }
+}
@@ -1,35 +1,805 @@
-use std::str::FromStr;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+mod bitboard;
 mod board;
 mod coordinate;
+mod engine;
 mod errors;
+mod evaluation;
 mod move_logic;
+mod ordering;
+mod perft;
+mod pgn;
 mod pieces;
+mod repl;
+mod san;
+mod transposition;
+mod uci;
+mod zobrist;
 
-use board::Board;
+use board::{Board, Epd, GameState, RenderStyle};
+use engine::{format_score, Engine, Limits};
 use errors::BoardError;
-use move_logic::Move;
+use pgn::{Game, PgnReader};
+use pieces::Colour;
+use repl::{Clock, Player, Repl};
+
+#[derive(Parser)]
+#[command(version, about = "Chess in your terminal!")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// How to draw the board
+    #[arg(long, global = true, value_enum, default_value_t = RenderStyle::Unicode)]
+    style: RenderStyle,
+    /// Draw the board from black's side
+    #[arg(long, global = true)]
+    flip: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play a game in the terminal (the default)
+    Play(PlayArgs),
+    /// Count the leaf nodes of the legal move tree, split by root move
+    Perft {
+        /// Position to start from, in FEN
+        fen: String,
+        /// Number of plies to search, at least 1
+        #[arg(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        depth: usize,
+    },
+    /// Time perft on the standard test positions, bitboard against mailbox move generation
+    Bench {
+        /// Number of plies to search
+        #[arg(long, default_value_t = 4)]
+        depth: usize,
+    },
+    /// Work with positions in FEN
+    Fen {
+        #[command(subcommand)]
+        command: FenCommand,
+    },
+    /// Work with games in PGN
+    Pgn {
+        #[command(subcommand)]
+        command: PgnCommand,
+    },
+    /// Work with EPD test suites
+    Epd {
+        #[command(subcommand)]
+        command: EpdCommand,
+    },
+    /// Let the engine evaluate a position
+    Analyse {
+        /// Position to analyse, in FEN (defaults to the initial position)
+        #[arg(long)]
+        fen: Option<String>,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// Size of the transposition table in MB
+        #[arg(long, value_name = "MB", default_value_t = transposition::DEFAULT_MEGABYTES)]
+        hash: usize,
+    },
+    /// Find the best move in puzzles from an EPD file, checked against their bm and am opcodes
+    Puzzle {
+        /// EPD file with one puzzle per line
+        file: String,
+        /// Attempts allowed for each puzzle
+        #[arg(long, default_value_t = 3)]
+        tries: usize,
+    },
+    /// Run the engine for a chess GUI, speaking the UCI protocol on stdin and stdout
+    Uci,
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    /// Position to start from, in FEN (defaults to the initial position)
+    #[arg(long)]
+    fen: Option<String>,
+    /// Who plays white
+    #[arg(long, value_enum, default_value_t = Player::Human)]
+    white: Player,
+    /// Who plays black
+    #[arg(long, value_enum, default_value_t = Player::Human)]
+    black: Player,
+    /// Save the game to a PGN file when it ends
+    #[arg(long, value_name = "FILE")]
+    save: Option<String>,
+    /// Play with a clock, giving each side this many minutes
+    #[arg(long, value_name = "MINUTES")]
+    clock: Option<u64>,
+    /// Seconds added to a side's clock after each of its moves
+    #[arg(long, value_name = "SECONDS", default_value_t = 0, requires = "clock")]
+    increment: u64,
+    #[command(flatten)]
+    limits: LimitArgs,
+    /// Size of the engine's transposition table in MB
+    #[arg(long, value_name = "MB", default_value_t = transposition::DEFAULT_MEGABYTES)]
+    hash: usize,
+}
+
+// what plain `chess-cli` plays, the same as `chess-cli play`
+impl Default for PlayArgs {
+    fn default() -> Self {
+        PlayArgs {
+            fen: None,
+            white: Player::Human,
+            black: Player::Human,
+            save: None,
+            clock: None,
+            increment: 0,
+            limits: LimitArgs::default(),
+            hash: transposition::DEFAULT_MEGABYTES,
+        }
+    }
+}
+
+// how long the engine may search, by default to a fixed depth
+#[derive(Args, Default)]
+struct LimitArgs {
+    /// Number of plies the engine searches
+    #[arg(long)]
+    depth: Option<usize>,
+    /// Milliseconds the engine spends on each move
+    #[arg(long, value_name = "MS")]
+    movetime: Option<u64>,
+    /// Number of positions the engine may visit
+    #[arg(long)]
+    nodes: Option<u64>,
+}
+
+impl LimitArgs {
+    fn limits(&self, default_depth: usize) -> Limits {
+        let mut limits = Limits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime: self.movetime.map(Duration::from_millis),
+            ..Limits::default()
+        };
+        if limits == Limits::default() {
+            limits.depth = Some(default_depth);
+        }
+        limits
+    }
+}
+
+#[derive(Subcommand)]
+enum FenCommand {
+    /// Check that a position is legal and show it
+    Validate {
+        /// Position in FEN, quoted or as separate words
+        #[arg(required = true, num_args = 1..)]
+        fen: Vec<String>,
+        /// Only check the syntax, allowing positions that can't occur in a game
+        #[arg(long)]
+        lenient: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PgnCommand {
+    /// Replay a game and show its tags, moves and final position
+    Show {
+        /// PGN file holding a single game
+        file: String,
+    },
+    /// Read every game in a PGN database and report the ones that are broken
+    Check {
+        /// PGN file holding any number of games
+        file: String,
+    },
+    /// Rewrite every readable game of a PGN database in another format
+    Convert {
+        /// PGN file holding any number of games
+        file: String,
+        /// What to write for each game
+        #[arg(long, value_enum, default_value_t = ConvertFormat::Pgn)]
+        to: ConvertFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConvertFormat {
+    /// Tidied up PGN, in export format
+    Pgn,
+    /// The final position of each game
+    Epd,
+}
+
+#[derive(Subcommand)]
+enum EpdCommand {
+    /// Let the engine solve each position of a test suite, checking its move against the bm
+    /// and am opcodes
+    Run {
+        /// EPD file with one position per line
+        file: String,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// Size of the transposition table in MB
+        #[arg(long, value_name = "MB", default_value_t = transposition::DEFAULT_MEGABYTES)]
+        hash: usize,
+    },
+}
+
+// global rendering options
+#[derive(Clone, Copy)]
+struct View {
+    style: RenderStyle,
+    flipped: bool,
+}
+
+impl View {
+    fn show(&self, board: &Board) {
+        print!("{}", board.render(self.style, self.flipped));
+    }
+}
 
 fn main() {
-    let result = run();
+    let cli = Cli::parse();
+    let view = View {
+        style: cli.style,
+        flipped: cli.flip,
+    };
+    let result = match cli.command.unwrap_or(Command::Play(PlayArgs::default())) {
+        Command::Play(args) => play(args, view),
+        Command::Perft { fen, depth } => perft(fen, depth),
+        Command::Bench { depth } => bench(depth),
+        Command::Fen {
+            command: FenCommand::Validate { fen, lenient },
+        } => fen_validate(fen.join(" "), lenient, view),
+        Command::Pgn {
+            command: PgnCommand::Show { file },
+        } => pgn_show(file, view),
+        Command::Pgn {
+            command: PgnCommand::Check { file },
+        } => pgn_check(file),
+        Command::Pgn {
+            command: PgnCommand::Convert { file, to, output },
+        } => pgn_convert(file, to, output),
+        Command::Epd {
+            command: EpdCommand::Run { file, limits, hash },
+        } => run_suite(file, limits.limits(6), hash),
+        Command::Puzzle { file, tries } => solve_positions(file, tries, view),
+        Command::Analyse { fen, limits, hash } => analyse(fen, limits.limits(6), hash, view),
+        Command::Uci => uci::Uci::new(io::stdout()).run(io::stdin().lock()),
+    };
+    // status goes to stderr so converted output can be piped
     match result {
-        Ok(_) => println!("finished!"),
+        Ok(_) => eprintln!("finished!"),
         Err(e) => eprintln!("{}", e),
     }
-    println!("exiting...")
+    eprintln!("exiting...")
+}
+
+fn file_error(file: &str, e: io::Error) -> BoardError {
+    BoardError::IoError(format!("{}: {}", file, e))
+}
+
+// interactive game on the terminal
+fn play(args: PlayArgs, view: View) -> Result<(), BoardError> {
+    let board = match args.fen {
+        Some(fen) => Board::new(fen)?,
+        None => Board::startpos(),
+    };
+    let mut repl = Repl::new(board, view.style, view.flipped);
+    repl.players = [args.white, args.black];
+    repl.hash = args.hash;
+    repl.clock = args.clock.map(|minutes| {
+        Clock::new(
+            Duration::from_secs(minutes * 60),
+            Duration::from_secs(args.increment),
+        )
+    });
+    // quick enough to play against in debug builds, with a clock the engine plays to it instead
+    repl.limits = match repl.clock {
+        Some(_) => args.limits.limits(engine::MAX_DEPTH),
+        None => args.limits.limits(4),
+    };
+    repl.game.set_tag("Event", "chess-cli game");
+    if let Some(minutes) = args.clock {
+        let control = format!("{}+{}", minutes * 60, args.increment);
+        repl.game.set_tag("TimeControl", &control);
+    }
+    for (tag, player) in [("White", args.white), ("Black", args.black)] {
+        if player == Player::Engine {
+            repl.game.set_tag(tag, "chess-cli");
+        }
+    }
+    repl.run(io::stdin().lock(), &mut io::stdout())
+        .map_err(|e| BoardError::IoError(e.to_string()))?;
+    if let Some(file) = args.save {
+        fs::write(&file, repl.game.to_string()).map_err(|e| file_error(&file, e))?;
+        println!("game saved to {}", file);
+    }
+    Ok(())
 }
 
-fn run() -> Result<(), BoardError> {
-    let mut board = Board::startpos();
-    println!("{}", board);
+fn analyse(fen: Option<String>, limits: Limits, hash: usize, view: View) -> Result<(), BoardError> {
+    let board = match fen {
+        Some(fen) => Board::new(fen)?,
+        None => Board::startpos(),
+    };
+    view.show(&board);
+    // scores are shown from white's side, as is usual for analysis
+    let sign = match board.active_colour() {
+        Colour::White => 1,
+        Colour::Black => -1,
+    };
+    let mut engine = Engine::new();
+    engine.set_hash_size(hash);
+    let result = engine.search_reporting(&board, &limits, |result| {
+        println!(
+            "depth {:>2}  {:>7}  {:<7} {:>10} nodes  {:.3}s",
+            result.depth,
+            format_score(sign * result.score),
+            board.to_san(&result.best_move),
+            result.nodes,
+            result.time.as_secs_f64()
+        );
+    })?;
+    println!("best move: {}", board.to_san(&result.best_move));
+    println!("score: {}", format_score(sign * result.score));
+    println!(
+        "{} nodes in {:.3}s",
+        result.nodes,
+        result.time.as_secs_f64()
+    );
+    println!(
+        "hash table: {}, {:.1}% full",
+        engine.table_stats(),
+        engine.hashfull() as f64 / 10.0
+    );
+    Ok(())
+}
 
-    let move_vec = vec![
-        "e2e4", "d7d5", "e4d5", "e7e5", "d5e6", "a7a5", "e6e7", "a5a4", "e7d8q", "a4a3", "d8d9",
+fn fen_validate(fen: String, lenient: bool, view: View) -> Result<(), BoardError> {
+    let board = match lenient {
+        true => Board::new_lenient(fen)?,
+        false => Board::new(fen)?,
+    };
+    view.show(&board);
+    println!("valid: {}", board.export_fen()?);
+    if board.status() != GameState::Active {
+        println!("{}", board.status());
+    }
+    Ok(())
+}
+
+fn perft(fen: String, depth: usize) -> Result<(), BoardError> {
+    let board = Board::new(fen)?;
+    let start = Instant::now();
+    let divide = board.divide(depth);
+    let elapsed = start.elapsed();
+    for (mv, nodes) in &divide {
+        println!("{}: {}", mv, nodes);
+    }
+    let total: usize = divide.iter().map(|(_, n)| n).sum();
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {:.3}s", elapsed.as_secs_f64());
+    Ok(())
+}
+
+// nodes per second of both move generators, which must agree on the node counts
+fn bench(depth: usize) -> Result<(), BoardError> {
+    let positions = [
+        ("startpos", perft::STARTPOS),
+        ("kiwipete", perft::KIWIPETE),
+        ("position 3", perft::POSITION_3),
+        ("position 4", perft::POSITION_4),
     ];
-    for mv_str in move_vec {
-        let mv = Move::from_str(mv_str)?;
-        board.process_move(&mv)?;
-        board.display_unicode();
+    // the magic tables are built on first use, keep that out of the timings
+    Board::startpos().perft(1);
+    println!(
+        "{:<12}{:>10}{:>14}{:>14}{:>9}",
+        "position", "nodes", "bitboard nps", "mailbox nps", "speedup"
+    );
+    let (mut total_bitboard, mut total_mailbox) = (0.0, 0.0);
+    for (name, fen) in positions {
+        let board = Board::new(fen.to_string())?;
+        let start = Instant::now();
+        let nodes = board.perft(depth);
+        let bitboard = start.elapsed().as_secs_f64();
+        let start = Instant::now();
+        let mailbox_nodes = board.perft_mailbox(depth);
+        let mailbox = start.elapsed().as_secs_f64();
+        if nodes != mailbox_nodes {
+            return Err(BoardError::EngineError(format!(
+                "{}: bitboard perft found {} nodes, mailbox perft {}",
+                name, nodes, mailbox_nodes
+            )));
+        }
+        println!(
+            "{:<12}{:>10}{:>14.0}{:>14.0}{:>8.1}x",
+            name,
+            nodes,
+            nodes as f64 / bitboard,
+            nodes as f64 / mailbox,
+            mailbox / bitboard
+        );
+        total_bitboard += bitboard;
+        total_mailbox += mailbox;
+    }
+    println!(
+        "bitboard {:.3}s, mailbox {:.3}s, {:.1}x faster",
+        total_bitboard,
+        total_mailbox,
+        total_mailbox / total_bitboard
+    );
+    Ok(())
+}
+
+// a position of a test suite with its best moves and moves to avoid, one of them not empty
+type SuitePosition = (Epd, Vec<move_logic::Move>, Vec<move_logic::Move>);
+
+// the positions of an EPD test suite with their line numbers, or why a line can't be used.
+// Blank lines and comments are skipped
+fn suite_positions(
+    text: &str,
+) -> impl Iterator<Item = (usize, Result<SuitePosition, String>)> + '_ {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let position = Epd::from_str(line)
+                .map_err(|e| e.to_string())
+                .and_then(|epd| match (epd.moves("bm"), epd.moves("am")) {
+                    (Ok(best), Ok(avoid)) if !best.is_empty() || !avoid.is_empty() => {
+                        Ok((epd, best, avoid))
+                    }
+                    (Err(e), _) | (_, Err(e)) => Err(e.to_string()),
+                    _ => Err("no bm or am to check against".to_string()),
+                });
+            (i + 1, position)
+        })
+}
+
+// the expected answer to a position of a test suite
+fn suite_answer(epd: &Epd, best: &[move_logic::Move], avoid: &[move_logic::Move]) -> String {
+    let san = |moves: &[move_logic::Move]| -> Vec<String> {
+        moves.iter().map(|m| epd.board.to_san(m)).collect()
+    };
+    match best.is_empty() {
+        false => format!("best move: {}", san(best).join(" ")),
+        true => format!("avoid: {}", san(avoid).join(" ")),
+    }
+}
+
+// the engine plays each position of a test suite within the limits, its move is checked
+// against the position's bm and am opcodes
+fn run_suite(file: String, limits: Limits, hash: usize) -> Result<(), BoardError> {
+    let text = fs::read_to_string(&file).map_err(|e| file_error(&file, e))?;
+    let mut engine = Engine::new();
+    engine.set_hash_size(hash);
+    let (mut solved, mut tried) = (0, 0);
+    for (line_number, position) in suite_positions(&text) {
+        let (epd, best, avoid) = match position {
+            Ok(position) => position,
+            Err(e) => {
+                println!("line {}: {}", line_number, e);
+                continue;
+            }
+        };
+        let id = epd.operands("id").and_then(|id| id.first()).cloned();
+        let id = id.unwrap_or(format!("line {}", line_number));
+        // each position is searched as if it were the first
+        engine.clear();
+        let result = match engine.search(&epd.board, &limits) {
+            Ok(result) => result,
+            Err(e) => {
+                println!("{}: {}", id, e);
+                continue;
+            }
+        };
+        tried += 1;
+        // the engine only plays legal moves, so they can always be checked
+        let correct = epd.check_move(&result.best_move)? == Some(true);
+        if correct {
+            solved += 1;
+        }
+        println!(
+            "{}: {} ({}) {}, {}",
+            id,
+            epd.board.to_san(&result.best_move),
+            format_score(result.score),
+            if correct { "correct" } else { "wrong" },
+            suite_answer(&epd, &best, &avoid)
+        );
+    }
+    println!("solved {}/{}", solved, tried);
+    Ok(())
+}
+
+// positions from an EPD file are solved one by one with moves read from stdin,
+// until the file or the input runs out. A wrong move can be retried up to `tries` times
+fn solve_positions(file: String, tries: usize, view: View) -> Result<(), BoardError> {
+    let text = fs::read_to_string(&file).map_err(|e| file_error(&file, e))?;
+    let mut input = io::stdin().lock().lines();
+    let (mut solved, mut tried) = (0, 0);
+    'positions: for (line_number, position) in suite_positions(&text) {
+        let (epd, best, avoid) = match position {
+            Ok(position) => position,
+            Err(e) => {
+                println!("line {}: {}", line_number, e);
+                continue;
+            }
+        };
+        view.show(&epd.board);
+        let colour = match epd.board.active_colour() {
+            Colour::White => "white",
+            Colour::Black => "black",
+        };
+        let id = epd.operands("id").and_then(|id| id.first()).cloned();
+        println!(
+            "{} ({} to move)",
+            id.unwrap_or(format!("line {}", line_number)),
+            colour
+        );
+        tried += 1;
+        let mut attempts = 0;
+        while attempts < tries {
+            print!("your move: ");
+            io::stdout()
+                .flush()
+                .map_err(|e| BoardError::IoError(e.to_string()))?;
+            let answer = match input.next() {
+                Some(Ok(answer)) => answer,
+                _ => {
+                    tried -= 1;
+                    break 'positions;
+                }
+            };
+            let mv = match epd.board.parse_move(answer.trim()) {
+                Ok(mv) if epd.board.legal_moves().contains(&mv) => mv,
+                Ok(mv) => {
+                    println!("{}", BoardError::IllegalMove(mv.to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+            attempts += 1;
+            // the move was legal, so it can always be checked
+            if epd.check_move(&mv)? == Some(true) {
+                solved += 1;
+                println!("correct!");
+                continue 'positions;
+            }
+            println!("wrong!");
+        }
+        println!("{}", suite_answer(&epd, &best, &avoid));
+    }
+    println!("solved {}/{}", solved, tried);
+    Ok(())
+}
+
+fn pgn_show(file: String, view: View) -> Result<(), BoardError> {
+    let text = fs::read_to_string(&file).map_err(|e| file_error(&file, e))?;
+    let game = Game::from_str(&text)?;
+    print!("{}", game);
+    let board = game.final_board()?;
+    view.show(&board);
+    if board.status() != GameState::Active {
+        println!("{}", board.status());
+    }
+    Ok(())
+}
+
+fn pgn_check(file: String) -> Result<(), BoardError> {
+    let reader = File::open(&file).map_err(|e| file_error(&file, e))?;
+    let (mut games, mut broken) = (0, 0);
+    for game in PgnReader::new(BufReader::new(reader)) {
+        games += 1;
+        if let Err(diagnostic) = game {
+            broken += 1;
+            println!("{}", diagnostic);
+        }
+    }
+    println!("{} games read, {} broken", games, broken);
+    Ok(())
+}
+
+// broken games are reported on stderr and left out
+fn pgn_convert(file: String, to: ConvertFormat, output: Option<String>) -> Result<(), BoardError> {
+    let reader = File::open(&file).map_err(|e| file_error(&file, e))?;
+    let mut writer: Box<dyn Write> = match &output {
+        Some(output) => Box::new(File::create(output).map_err(|e| file_error(output, e))?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let (mut converted, mut broken) = (0, 0);
+    for game in PgnReader::new(BufReader::new(reader)) {
+        let game = match game {
+            Ok(game) => game,
+            Err(diagnostic) => {
+                broken += 1;
+                eprintln!("{}", diagnostic);
+                continue;
+            }
+        };
+        let text = match to {
+            ConvertFormat::Pgn => format!("{}\n", game),
+            ConvertFormat::Epd => {
+                let mut epd = Epd {
+                    board: game.final_board()?,
+                    operations: vec![],
+                };
+                if let Some(event) = game.tag("Event") {
+                    epd.operations
+                        .push(("id".to_string(), vec![event.to_string()]));
+                }
+                format!("{}\n", epd)
+            }
+        };
+        writer
+            .write_all(text.as_bytes())
+            .map_err(|e| BoardError::IoError(e.to_string()))?;
+        converted += 1;
     }
-    println!("{:?}", board);
+    eprintln!("{} games converted, {} broken", converted, broken);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    fn parse(args: &str) -> Cli {
+        Cli::try_parse_from(args.split_whitespace()).unwrap()
+    }
+
+    fn rejects(args: &str) -> bool {
+        Cli::try_parse_from(args.split_whitespace()).is_err()
+    }
+
+    #[test]
+    fn cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn play_by_default() {
+        let cli = parse("chess-cli");
+        assert!(cli.command.is_none());
+        assert_eq!(cli.style, RenderStyle::Unicode);
+        assert!(!cli.flip);
+        let cli = parse("chess-cli play --black engine --clock 5 --increment 3 --depth 2");
+        match cli.command {
+            Some(Command::Play(args)) => {
+                assert!(matches!(
+                    (args.white, args.black),
+                    (Player::Human, Player::Engine)
+                ));
+                assert_eq!((args.clock, args.increment), (Some(5), 3));
+                assert_eq!(args.limits.depth, Some(2));
+                assert_eq!(args.hash, transposition::DEFAULT_MEGABYTES);
+            }
+            _ => panic!("expected play"),
+        }
+        // plain chess-cli plays with the same defaults
+        match parse("chess-cli play").command {
+            Some(Command::Play(args)) => {
+                let default = PlayArgs::default();
+                assert_eq!((args.white, args.black), (default.white, default.black));
+                assert_eq!((args.clock, args.hash), (default.clock, default.hash));
+            }
+            _ => panic!("expected play"),
+        }
+        // an increment means nothing without a clock
+        assert!(rejects("chess-cli play --increment 3"));
+        assert!(rejects("chess-cli play --white nobody"));
+    }
+
+    #[test]
+    fn global_view_options() {
+        // before or after the subcommand
+        let cli = parse("chess-cli --style ascii pgn show game.pgn --flip");
+        assert_eq!(cli.style, RenderStyle::Ascii);
+        assert!(cli.flip);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Pgn {
+                command: PgnCommand::Show { .. }
+            })
+        ));
+        assert_eq!(
+            parse("chess-cli --style no-colour").style,
+            RenderStyle::NoColour
+        );
+        assert!(rejects("chess-cli --style fancy"));
+    }
+
+    #[test]
+    fn subcommands() {
+        match parse("chess-cli fen validate 8/8/8/8/8/8/8/K6k w - - 0 1 --lenient").command {
+            Some(Command::Fen {
+                command: FenCommand::Validate { fen, lenient },
+            }) => {
+                assert_eq!(fen.len(), 6);
+                assert!(lenient);
+            }
+            _ => panic!("expected fen validate"),
+        }
+        assert!(rejects("chess-cli fen validate"));
+        match parse("chess-cli pgn convert games.pgn --to epd -o out.epd").command {
+            Some(Command::Pgn {
+                command: PgnCommand::Convert { file, to, output },
+            }) => {
+                assert_eq!(file, "games.pgn");
+                assert!(matches!(to, ConvertFormat::Epd));
+                assert_eq!(output.as_deref(), Some("out.epd"));
+            }
+            _ => panic!("expected pgn convert"),
+        }
+        match parse("chess-cli analyse --movetime 500 --hash 64").command {
+            Some(Command::Analyse { fen, limits, hash }) => {
+                assert_eq!(fen, None);
+                assert_eq!(limits.movetime, Some(500));
+                assert_eq!(hash, 64);
+            }
+            _ => panic!("expected analyse"),
+        }
+        match parse("chess-cli puzzle puzzles.epd").command {
+            Some(Command::Puzzle { file, tries }) => {
+                assert_eq!((file.as_str(), tries), ("puzzles.epd", 3))
+            }
+            _ => panic!("expected puzzle"),
+        }
+        match parse("chess-cli epd run wac.epd --nodes 10000").command {
+            Some(Command::Epd {
+                command: EpdCommand::Run { file, limits, hash },
+            }) => {
+                assert_eq!(file, "wac.epd");
+                assert_eq!(limits.nodes, Some(10_000));
+                assert_eq!(hash, transposition::DEFAULT_MEGABYTES);
+            }
+            _ => panic!("expected epd run"),
+        }
+        assert!(matches!(parse("chess-cli uci").command, Some(Command::Uci)));
+        assert!(rejects("chess-cli uci --depth 3"));
+        assert!(rejects("chess-cli perft startpos"));
+        // perft counts the position itself at depth 0, which has no moves to divide it by
+        assert!(rejects("chess-cli perft 4k3/8/8/8/8/8/8/4K3 0"));
+        assert!(matches!(
+            parse("chess-cli perft 4k3/8/8/8/8/8/8/4K3 1").command,
+            Some(Command::Perft { depth: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn engine_limits() {
+        // nothing given searches to the default depth
+        let limits = LimitArgs::default().limits(6);
+        assert_eq!(limits.depth, Some(6));
+        assert_eq!((limits.nodes, limits.movetime), (None, None));
+        // any limit given replaces it
+        let limits = LimitArgs {
+            movetime: Some(250),
+            ..LimitArgs::default()
+        }
+        .limits(6);
+        assert_eq!(limits.depth, None);
+        assert_eq!(limits.movetime, Some(Duration::from_millis(250)));
+        let limits = LimitArgs {
+            depth: Some(2),
+            nodes: Some(100),
+            ..LimitArgs::default()
+        }
+        .limits(6);
+        assert_eq!((limits.depth, limits.nodes), (Some(2), Some(100)));
+    }
+}
@@ -2,13 +2,14 @@ use std::fmt::{self, Display};
 use std::str::FromStr;
 
 use crate::{
+    board::CastlingRights,
     coordinate::Coordinate,
     errors::BoardError,
     pieces::{Colour, Piece, PieceType},
 };
 
 // move struct that provides all necessary information for a uci move
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Move {
     pub from: Coordinate,
     pub destination: Coordinate,
@@ -47,12 +48,20 @@ impl FromStr for Move {
 
 impl Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}", self.from, self.destination)
+        write!(f, "{}{}", self.from, self.destination)?;
+        if let Some(piece_type) = self.promotion {
+            let piece = Piece {
+                piece_type,
+                colour: Colour::Black,
+            };
+            write!(f, "{}", char::from(piece))?;
+        }
+        Ok(())
     }
 }
 
 // move logic
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 #[allow(dead_code)]
 pub enum MoveType {
     Regular,
@@ -74,6 +83,7 @@ pub fn find_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     index: usize,
     en_passant_target: &Option<Coordinate>,
+    castling_rights: &CastlingRights,
 ) {
     let piece = squares[index].unwrap();
 
@@ -101,47 +111,113 @@ pub fn find_legal_moves(
         }
         PieceType::King => {
             get_king_legal_moves(legal_moves, squares, index, &piece.colour);
+            get_castling_moves(legal_moves, squares, index, &piece.colour, castling_rights);
         }
     }
 }
 
 // is given colour currently in check?
 pub fn in_check(this_colour: &Colour, squares: &[Option<Piece>]) -> bool {
-    let opp_colour = match this_colour {
-        Colour::White => Colour::Black,
-        Colour::Black => Colour::White,
-    };
+    let mut opp_colour = *this_colour;
+    opp_colour.change_colour();
 
-    let mut legal_moves: Vec<(usize, MoveType)> = vec![];
+    let king_location = squares.iter().position(
+        |x| matches!(x, Some(p) if p.piece_type == PieceType::King && p.colour == *this_colour),
+    );
 
-    // iterator of all pieces
-    let piece_it = squares
-        .iter()
-        .enumerate()
-        .filter(|(_i, &x)| x.is_some())
-        .map(|(_i, x)| (_i, x.unwrap()));
-
-    let (king_location, _) = piece_it
-        .clone()
-        .find(|(_, x)| x.piece_type == PieceType::King && x.colour == *this_colour)
-        .unwrap();
+    // positions set up for analysis might not have a king
+    match king_location {
+        Some(index) => is_square_attacked(squares, index, &opp_colour),
+        None => false,
+    }
+}
 
-    piece_it
-        .filter(|(_i, x)| x.colour == opp_colour)
-        // ^ an iterator of opponent piece indexes ^
-        .for_each(|(i, _)| find_legal_moves(squares, &mut legal_moves, i, &None)); // king cannot exist in en passant target sq
+// can any piece of colour `by` capture on squares[index]?
+// works outwards from the target square: a knight on `index` that could capture an enemy
+// knight means that knight is attacking `index`, and likewise for every other piece type
+pub fn is_square_attacked(squares: &[Option<Piece>], index: usize, by: &Colour) -> bool {
+    let mut defender = *by;
+    defender.change_colour();
+
+    let attacked_by = |moves: &[(usize, MoveType)], piece_types: &[PieceType]| {
+        moves.iter().any(|(i, _)| match squares[*i] {
+            Some(p) => p.colour == *by && piece_types.contains(&p.piece_type),
+            None => false,
+        })
+    };
 
-    for (index, _) in legal_moves {
-        if king_location == index {
-            return true;
+    // pawns: a white pawn attacks diagonally upwards, so look diagonally downwards for it
+    let behind: i8 = match by {
+        Colour::White => -1,
+        Colour::Black => 1,
+    };
+    let pawn_sources = [offset(index, -1, behind), offset(index, 1, behind)];
+    for source in pawn_sources.into_iter().flatten() {
+        if let Some(p) = squares[source] {
+            if p.colour == *by && p.piece_type == PieceType::Pawn {
+                return true;
+            }
         }
     }
 
-    false
+    let mut moves: Vec<(usize, MoveType)> = vec![];
+    get_knight_legal_moves(&mut moves, squares, index, &defender);
+    if attacked_by(&moves, &[PieceType::Knight]) {
+        return true;
+    }
+    moves.clear();
+    get_king_legal_moves(&mut moves, squares, index, &defender);
+    if attacked_by(&moves, &[PieceType::King]) {
+        return true;
+    }
+    moves.clear();
+    get_bishop_legal_moves(&mut moves, squares, index, &defender);
+    if attacked_by(&moves, &[PieceType::Bishop, PieceType::Queen]) {
+        return true;
+    }
+    moves.clear();
+    get_rook_legal_moves(&mut moves, squares, index, &defender);
+    attacked_by(&moves, &[PieceType::Rook, PieceType::Queen])
 }
 
 // Individual piece logic
 
+// (file, rank) steps for each piece, applied with `offset` so they can never wrap around an edge
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const KING_STEPS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+// index of the square reached by moving d_file files and d_rank ranks from index
+// None if that square is off the board
+fn offset(index: usize, d_file: i8, d_rank: i8) -> Option<usize> {
+    let file = (index % 8) as i8 + d_file;
+    let rank = (index / 8) as i8 + d_rank;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((file + rank * 8) as usize)
+    } else {
+        None
+    }
+}
+
 fn get_pawn_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
@@ -149,393 +225,148 @@ fn get_pawn_legal_moves(
     colour: &Colour,
     en_passant_target: &Option<Coordinate>,
 ) {
-    match colour {
-        Colour::White => {
-            // regular moves
-            let mut target = piece_index + 8;
-            if squares[target].is_none() {
-                // check for promotion
-                if (56..64).contains(&target) {
-                    legal_moves.push((target, MoveType::PromotionPush));
-                } else {
-                    legal_moves.push((target, MoveType::Regular));
-                    // if piece on starting file and can move 1 extra space -> can double move
-                    target += 8;
-                    if (8..16).contains(&piece_index) && squares[target].is_none() {
-                        legal_moves.push((target, MoveType::DoublePush(target - 8)));
-                    }
-                }
-            }
-            // up left capture
-            // if not on a-file
-            if &piece_index % 8 != 0 {
-                target = piece_index + 7;
-                if let Some(piece) = squares[target] {
-                    if piece.colour == Colour::Black {
-                        // check for promotion
-                        if (56..64).contains(&target) {
-                            legal_moves.push((target, MoveType::PromotionCapture));
-                        } else {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                    }
-                }
-                // check for en passant
-                else if let Some(coord) = en_passant_target {
-                    if target == coord.into() {
-                        legal_moves.push((target, MoveType::EnPassant(target - 8)));
-                    }
-                }
-            }
+    // direction of travel, rank the pawn starts on and rank it promotes on
+    let (forward, start_rank, promotion_rank) = match colour {
+        Colour::White => (1i8, 1usize, 7usize),
+        Colour::Black => (-1i8, 6usize, 0usize),
+    };
 
-            // up right capture
-            // if piece is not on h-file
-            if &piece_index % 8 != 7 {
-                target = piece_index + 9;
-                if let Some(piece) = squares[target] {
-                    if piece.colour == Colour::Black {
-                        // check for promotion
-                        if (56..64).contains(&target) {
-                            legal_moves.push((target, MoveType::PromotionCapture));
-                        } else {
-                            legal_moves.push((target, MoveType::Capture));
+    // regular moves
+    if let Some(target) = offset(piece_index, 0, forward) {
+        if squares[target].is_none() {
+            // check for promotion
+            if target / 8 == promotion_rank {
+                legal_moves.push((target, MoveType::PromotionPush));
+            } else {
+                legal_moves.push((target, MoveType::Regular));
+                // if piece on starting rank and can move 1 extra space -> can double move
+                if piece_index / 8 == start_rank {
+                    if let Some(double) = offset(piece_index, 0, 2 * forward) {
+                        if squares[double].is_none() {
+                            legal_moves.push((double, MoveType::DoublePush(target)));
                         }
                     }
                 }
-                // check for en passant
-                else if let Some(coord) = en_passant_target {
-                    if target == coord.into() {
-                        legal_moves.push((target, MoveType::EnPassant(target - 8)));
-                    }
-                }
             }
         }
-        Colour::Black => {
-            // regular moves
-            let mut target = piece_index - 8;
-            if squares[target].is_none() {
+    }
+
+    // diagonal captures, offset() keeps a- and h-file pawns from wrapping
+    for d_file in [-1i8, 1] {
+        let target = match offset(piece_index, d_file, forward) {
+            Some(target) => target,
+            None => continue,
+        };
+        if let Some(piece) = squares[target] {
+            if &piece.colour != colour {
                 // check for promotion
-                if (0..8).contains(&target) {
-                    legal_moves.push((target, MoveType::PromotionPush));
+                if target / 8 == promotion_rank {
+                    legal_moves.push((target, MoveType::PromotionCapture));
                 } else {
-                    legal_moves.push((target, MoveType::Regular));
-                    // if piece on starting file and can move 1 extra space -> can double move
-                    if (48..56).contains(&piece_index) {
-                        target -= 8;
-                        if squares[target].is_none() {
-                            legal_moves.push((target, MoveType::DoublePush(target + 8)));
-                        }
-                    }
+                    legal_moves.push((target, MoveType::Capture));
                 }
             }
-
-            // down right capture
-
-            // if target on h file then not allowed
-            if &piece_index % 8 != 7 {
-                target = &piece_index - 7;
-                if let Some(piece) = squares[target] {
-                    if piece.colour == Colour::White {
-                        if (0..8).contains(&target) {
-                            legal_moves.push((target, MoveType::PromotionCapture));
-                        } else {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                    }
-                }
-                // check for en passant
-                else if let Some(coord) = en_passant_target {
-                    if target == coord.into() {
-                        legal_moves.push((target, MoveType::EnPassant(target + 8)));
-                    }
-                }
-            }
-
-            // down left capture
-            //if is not on a-file
-            if &piece_index % 8 != 0 {
-                target = &piece_index - 9;
-                if let Some(piece) = squares[target] {
-                    if piece.colour == Colour::White {
-                        if (0..8).contains(&target) {
-                            legal_moves.push((target, MoveType::PromotionCapture));
-                        } else {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                    }
-                }
-                // check for en passant
-                else if let Some(coord) = en_passant_target {
-                    if target == coord.into() {
-                        legal_moves.push((target, MoveType::EnPassant(target + 8)));
-                    }
-                }
+        }
+        // check for en passant
+        else if let Some(coord) = en_passant_target {
+            if target == coord.into() {
+                // captured pawn sits beside the moving pawn, behind the target square
+                let captured = offset(target, 0, -forward).unwrap();
+                legal_moves.push((target, MoveType::EnPassant(captured)));
             }
         }
     }
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
-fn get_bishop_legal_moves(
+// add a move to target if it is empty or holds an opponent piece
+// returns true if the square was empty (sliding pieces may continue)
+fn push_step(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
-    piece_index: usize,
+    target: usize,
     colour: &Colour,
-) {
-    let mut col = *colour;
-    col.change_colour();
-
-    let range = 0..64usize;
-
-    let mut target: usize;
-
-    // search up-right
-    // not on h file
-    if piece_index % 8 != 7 {
-        for x in 1..8usize {
-            target = piece_index + (9 * x);
-            // not wrapped h->a file
-            if range.contains(&target) && target % 8 != 0 {
-                match squares[target] {
-                    // hit piece
-                    Some(piece) => {
-                        if piece.colour == col {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                        break;
-                    }
-                    None => {
-                        legal_moves.push((target, MoveType::Regular));
-                    }
-                }
-            } else {
-                // out of range
-                break;
+) -> bool {
+    match squares[target] {
+        Some(piece) => {
+            if &piece.colour != colour {
+                legal_moves.push((target, MoveType::Capture));
             }
+            false
         }
-    }
-
-    // down-right
-    // not on h file
-    if piece_index % 8 != 7 {
-        for x in 1..8usize {
-            if let Some(target) = usize::checked_sub(piece_index + x, 8 * x) {
-                // not wrapped h->a file
-                if target % 8 != 0 {
-                    match squares[target] {
-                        // hit piece
-                        Some(piece) => {
-                            if piece.colour == col {
-                                legal_moves.push((target, MoveType::Capture));
-                            }
-                            break;
-                        }
-                        None => {
-                            legal_moves.push((target, MoveType::Regular));
-                        }
-                    }
-                } else {
-                    // out of range
-                    break;
-                }
-            }
+        None => {
+            legal_moves.push((target, MoveType::Regular));
+            true
         }
     }
+}
 
-    // search up-left
-    // not on a file
-    if piece_index % 8 != 0 {
-        for x in 1..8usize {
-            target = piece_index + (8 * x) - x;
-            // not wrapped a->h file
-            if range.contains(&target) && target % 8 != 7 {
-                match squares[target] {
-                    // hit piece
-                    Some(piece) => {
-                        if piece.colour == col {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                        break;
-                    }
-                    None => {
-                        legal_moves.push((target, MoveType::Regular));
-                    }
-                }
-            } else {
-                // out of range
+// walk each direction until the edge of the board or the first piece
+fn get_sliding_moves(
+    legal_moves: &mut Vec<(usize, MoveType)>,
+    squares: &[Option<Piece>],
+    piece_index: usize,
+    colour: &Colour,
+    directions: &[(i8, i8)],
+) {
+    for (d_file, d_rank) in directions {
+        let mut current = piece_index;
+        while let Some(target) = offset(current, *d_file, *d_rank) {
+            if !push_step(legal_moves, squares, target, colour) {
                 break;
             }
+            current = target;
         }
     }
+}
 
-    // search down-left
-    // not on a file
-    if piece_index % 8 != 0 {
-        for x in 1..8usize {
-            if let Some(target) = usize::checked_sub(piece_index, 9 * x) {
-                // not wrapped a->h file
-                if target % 8 != 7 {
-                    match squares[target] {
-                        // hit piece
-                        Some(piece) => {
-                            if piece.colour == col {
-                                legal_moves.push((target, MoveType::Capture));
-                            }
-                            break;
-                        }
-                        None => {
-                            legal_moves.push((target, MoveType::Regular));
-                        }
-                    }
-                } else {
-                    // out of range
-                    break;
-                }
-            }
+// jump to each step if it stays on the board
+fn get_stepping_moves(
+    legal_moves: &mut Vec<(usize, MoveType)>,
+    squares: &[Option<Piece>],
+    piece_index: usize,
+    colour: &Colour,
+    steps: &[(i8, i8)],
+) {
+    for (d_file, d_rank) in steps {
+        if let Some(target) = offset(piece_index, *d_file, *d_rank) {
+            push_step(legal_moves, squares, target, colour);
         }
     }
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
+fn get_bishop_legal_moves(
+    legal_moves: &mut Vec<(usize, MoveType)>,
+    squares: &[Option<Piece>],
+    piece_index: usize,
+    colour: &Colour,
+) {
+    get_sliding_moves(
+        legal_moves,
+        squares,
+        piece_index,
+        colour,
+        &BISHOP_DIRECTIONS,
+    );
+}
+
 fn get_knight_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
     piece_index: usize,
     colour: &Colour,
 ) {
-    let range = 0..64i8;
-    let k: [i8; 8] = [6, 10, 15, 17, -6, -10, -15, -17];
-
-    let mut target: i8;
-    for x in k {
-        target = piece_index as i8 + x;
-        if range.contains(&target) {
-            match squares[target as usize] {
-                Some(piece) => {
-                    if &piece.colour != colour {
-                        legal_moves.push((target as usize, MoveType::Capture));
-                    }
-                }
-                None => {
-                    legal_moves.push((target as usize, MoveType::Regular));
-                }
-            }
-        }
-    }
+    get_stepping_moves(legal_moves, squares, piece_index, colour, &KNIGHT_STEPS);
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
 fn get_rook_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
     piece_index: usize,
     colour: &Colour,
 ) {
-    let mut col = *colour;
-    col.change_colour();
-
-    let range = 0..64usize;
-
-    let mut target: usize;
-
-    // search up
-    for x in 1..8usize {
-        target = piece_index + (8 * x);
-        if range.contains(&target) {
-            match squares[target] {
-                // hit piece
-                Some(piece) => {
-                    if piece.colour == col {
-                        legal_moves.push((target, MoveType::Capture));
-                    }
-                    break;
-                }
-                None => {
-                    legal_moves.push((target, MoveType::Regular));
-                }
-            }
-        } else {
-            // out of range
-            break;
-        }
-    }
-
-    // search down
-    for x in 1..8usize {
-        if let Some(target) = usize::checked_sub(piece_index, 8 * x) {
-            match squares[target] {
-                // hit piece
-                Some(piece) => {
-                    if piece.colour == col {
-                        legal_moves.push((target, MoveType::Capture));
-                    }
-                    break;
-                }
-                None => {
-                    legal_moves.push((target, MoveType::Regular));
-                }
-            }
-        }
-    }
-
-    // search right
-    // not on h file
-    if piece_index % 8 != 7 {
-        for x in 1..8usize {
-            target = piece_index + x;
-            // not wrapped from h->a file
-            if target % 8 != 0 {
-                match squares[target] {
-                    // hit piece
-                    Some(piece) => {
-                        if piece.colour == col {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                        break;
-                    }
-                    None => {
-                        legal_moves.push((target, MoveType::Regular));
-                    }
-                }
-            } else {
-                // out of range
-                break;
-            }
-        }
-    }
-
-    // search left
-    // not on a file
-    if piece_index % 8 != 0 {
-        for x in 1..8usize {
-            let target = piece_index - x;
-            // not wrapped from a->h file
-            if target % 8 != 7 {
-                match squares[target] {
-                    // hit piece
-                    Some(piece) => {
-                        if piece.colour == col {
-                            legal_moves.push((target, MoveType::Capture));
-                        }
-                        break;
-                    }
-                    None => {
-                        legal_moves.push((target, MoveType::Regular));
-                    }
-                }
-            } else {
-                // out of range
-                break;
-            }
-        }
-    }
+    get_sliding_moves(legal_moves, squares, piece_index, colour, &ROOK_DIRECTIONS);
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
 fn get_queen_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
@@ -546,32 +377,61 @@ fn get_queen_legal_moves(
     get_rook_legal_moves(legal_moves, squares, piece_index, colour);
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
 fn get_king_legal_moves(
     legal_moves: &mut Vec<(usize, MoveType)>,
     squares: &[Option<Piece>],
     piece_index: usize,
     colour: &Colour,
 ) {
-    let range = 0..64i8;
-    let k: [i8; 8] = [7, 8, 9, 1, -7, -8, -9, -1];
-
-    let mut target: i8;
-    for x in k {
-        target = piece_index as i8 + x;
-        if range.contains(&target) {
-            match squares[target as usize] {
-                Some(piece) => {
-                    if &piece.colour != colour {
-                        legal_moves.push((target as usize, MoveType::Capture));
-                    }
-                }
-                None => {
-                    legal_moves.push((target as usize, MoveType::Regular));
-                }
-            }
-        }
+    get_stepping_moves(legal_moves, squares, piece_index, colour, &KING_STEPS);
+}
+
+// castling is only possible when:
+// - the side still has the right to castle on that side
+// - king and rook stand on their original squares
+// - all squares between them are empty
+// - the king is not in check and does not pass through or land on an attacked square
+fn get_castling_moves(
+    legal_moves: &mut Vec<(usize, MoveType)>,
+    squares: &[Option<Piece>],
+    piece_index: usize,
+    colour: &Colour,
+    castling_rights: &CastlingRights,
+) {
+    let (king_side, queen_side, king_start) = match colour {
+        Colour::White => (castling_rights.k_w, castling_rights.q_w, 4usize),
+        Colour::Black => (castling_rights.k_b, castling_rights.q_b, 60usize),
+    };
+    if piece_index != king_start || !(king_side || queen_side) {
+        return;
+    }
+    let mut opp_colour = *colour;
+    opp_colour.change_colour();
+
+    let is_own_rook = |i: usize| matches!(squares[i], Some(p) if p.piece_type == PieceType::Rook && p.colour == *colour);
+    let is_empty = |i: &usize| squares[*i].is_none();
+    let is_safe = |i: &usize| !is_square_attacked(squares, *i, &opp_colour);
+
+    if !is_safe(&king_start) {
+        return;
+    }
+    // king side: f and g files empty and safe
+    if king_side
+        && is_own_rook(king_start + 3)
+        && [king_start + 1, king_start + 2].iter().all(is_empty)
+        && [king_start + 1, king_start + 2].iter().all(is_safe)
+    {
+        legal_moves.push((king_start + 2, MoveType::CastleKingSide));
+    }
+    // queen side: b, c and d files empty, only c and d need to be safe
+    if queen_side
+        && is_own_rook(king_start - 4)
+        && [king_start - 1, king_start - 2, king_start - 3]
+            .iter()
+            .all(is_empty)
+        && [king_start - 1, king_start - 2].iter().all(is_safe)
+    {
+        legal_moves.push((king_start - 2, MoveType::CastleQueenSide));
     }
 }
 
@@ -599,4 +459,151 @@ mod tests {
         };
         assert_eq!(mv, Move::from_str("e7e8q").unwrap());
     }
+
+    fn empty_board() -> Vec<Option<Piece>> {
+        vec![None; 64]
+    }
+
+    fn no_castling() -> CastlingRights {
+        CastlingRights::from_str("-").unwrap()
+    }
+
+    // sorted destinations of the piece standing on `index`
+    fn destinations(squares: &[Option<Piece>], index: usize) -> Vec<usize> {
+        let mut legal_moves: Vec<(usize, MoveType)> = vec![];
+        find_legal_moves(squares, &mut legal_moves, index, &None, &no_castling());
+        let mut targets: Vec<usize> = legal_moves.into_iter().map(|(i, _)| i).collect();
+        targets.sort();
+        targets
+    }
+
+    // file and rank distance between two squares
+    fn distance(a: usize, b: usize) -> (usize, usize) {
+        ((a % 8).abs_diff(b % 8), (a / 8).abs_diff(b / 8))
+    }
+
+    // place `piece` alone on every square of an empty board and compare its moves against
+    // every square satisfying `reachable`, so any wraparound shows up as an extra target
+    fn check_every_square(c: char, reachable: fn(usize, usize) -> bool) {
+        let piece = Piece::try_from(c).unwrap();
+        for from in 0..64usize {
+            let mut squares = empty_board();
+            squares[from] = Some(piece);
+            let expected: Vec<usize> = (0..64usize)
+                .filter(|to| *to != from && reachable(*to, from))
+                .collect();
+            assert_eq!(
+                destinations(&squares, from),
+                expected,
+                "{} on {}",
+                c,
+                Coordinate::try_from(from).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn knight_from_every_square() {
+        check_every_square('N', |a, b| matches!(distance(a, b), (1, 2) | (2, 1)));
+        check_every_square('n', |a, b| matches!(distance(a, b), (1, 2) | (2, 1)));
+    }
+
+    #[test]
+    fn king_from_every_square() {
+        check_every_square('K', |a, b| {
+            let (f, r) = distance(a, b);
+            f <= 1 && r <= 1
+        });
+        check_every_square('k', |a, b| {
+            let (f, r) = distance(a, b);
+            f <= 1 && r <= 1
+        });
+    }
+
+    #[test]
+    fn bishop_from_every_square() {
+        check_every_square('B', |a, b| {
+            let (f, r) = distance(a, b);
+            f == r
+        });
+    }
+
+    #[test]
+    fn rook_from_every_square() {
+        check_every_square('r', |a, b| {
+            let (f, r) = distance(a, b);
+            f == 0 || r == 0
+        });
+    }
+
+    #[test]
+    fn queen_from_every_square() {
+        check_every_square('Q', |a, b| {
+            let (f, r) = distance(a, b);
+            f == r || f == 0 || r == 0
+        });
+    }
+
+    #[test]
+    fn pawn_from_every_square() {
+        for (c, enemy, forward, start_rank) in [('P', 'n', 1i8, 1usize), ('p', 'N', -1i8, 6usize)] {
+            for from in 8..56usize {
+                let mut squares = empty_board();
+                squares[from] = Some(Piece::try_from(c).unwrap());
+                // enemy pieces either side of the square in front so both captures are available
+                let ahead = offset(from, 0, forward).unwrap();
+                for side in [offset(ahead, -1, 0), offset(ahead, 1, 0)]
+                    .into_iter()
+                    .flatten()
+                {
+                    squares[side] = Some(Piece::try_from(enemy).unwrap());
+                }
+                let expected: Vec<usize> = (0..64usize)
+                    .filter(|to| {
+                        let (f, r) = distance(*to, from);
+                        let is_forward = (*to as i8 / 8 - from as i8 / 8).signum() == forward;
+                        let single = is_forward && r == 1 && f <= 1;
+                        let double = is_forward && r == 2 && f == 0 && from / 8 == start_rank;
+                        single || double
+                    })
+                    .collect();
+                assert_eq!(
+                    destinations(&squares, from),
+                    expected,
+                    "{} on {}",
+                    c,
+                    Coordinate::try_from(from).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sliding_pieces_blocked() {
+        // rook on d4, own pawn on d6, enemy pawn on f4
+        let mut squares = empty_board();
+        squares[27] = Some(Piece::try_from('R').unwrap());
+        squares[43] = Some(Piece::try_from('P').unwrap());
+        squares[29] = Some(Piece::try_from('p').unwrap());
+        let mut legal_moves: Vec<(usize, MoveType)> = vec![];
+        find_legal_moves(&squares, &mut legal_moves, 27, &None, &no_castling());
+        assert!(legal_moves.contains(&(35, MoveType::Regular)));
+        assert!(!legal_moves.iter().any(|(i, _)| *i == 43 || *i == 51));
+        assert!(legal_moves.contains(&(29, MoveType::Capture)));
+        assert!(!legal_moves.iter().any(|(i, _)| *i == 30));
+    }
+
+    #[test]
+    fn pawn_edge_captures_do_not_wrap() {
+        // white pawn on h2 with a black piece on a4 (index 15 + 9 == 24)
+        let mut squares = empty_board();
+        squares[15] = Some(Piece::try_from('P').unwrap());
+        squares[24] = Some(Piece::try_from('n').unwrap());
+        assert_eq!(destinations(&squares, 15), vec![23, 31]);
+        // black pawn on a7 with a white piece on h5 (index 48 - 9 == 39)
+        let mut squares = empty_board();
+        squares[48] = Some(Piece::try_from('p').unwrap());
+        squares[39] = Some(Piece::try_from('N').unwrap());
+        assert_eq!(destinations(&squares, 48), vec![32, 40]);
+    }
 }
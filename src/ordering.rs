@@ -0,0 +1,293 @@
+use std::cmp::Reverse;
+
+use crate::{
+    bitboard::Bitboard,
+    board::Board,
+    move_logic::{Move, MoveType},
+    pieces::{Colour, PieceType},
+};
+
+// alpha-beta cuts off sooner the earlier it sees the best move, so moves are searched in order of
+// how likely they are to be good: the move the transposition table remembers, promotions,
+// captures that don't lose material, then quiet moves that caused cutoffs elsewhere
+
+// rough piece values for weighing up captures, indexed by PieceType
+const SEE_VALUES: [i32; 6] = [100, 330, 320, 500, 900, 20_000];
+// cheapest first, the order pieces join an exchange in
+const EXCHANGE_ORDER: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+const HASH_MOVE: i32 = 1_000_000;
+const PROMOTION: i32 = 600_000;
+const GOOD_CAPTURE: i32 = 500_000;
+const KILLER: i32 = 300_000;
+// quiet moves score their history, kept below this by halving every entry when one passes it
+const HISTORY_LIMIT: i32 = 100_000;
+const BAD_CAPTURE: i32 = -500_000;
+const UNDERPROMOTION: i32 = -600_000;
+
+fn is_quiet(move_type: MoveType) -> bool {
+    !matches!(
+        move_type,
+        MoveType::Capture
+            | MoveType::EnPassant(_)
+            | MoveType::PromotionPush
+            | MoveType::PromotionCapture
+    )
+}
+
+fn value(piece_type: PieceType) -> i32 {
+    SEE_VALUES[piece_type as usize]
+}
+
+fn moved_piece(board: &Board, mv: &Move) -> PieceType {
+    board
+        .piece_at(mv.from)
+        .map_or(PieceType::Pawn, |piece| piece.piece_type)
+}
+
+fn captured_piece(board: &Board, mv: &Move, move_type: MoveType) -> Option<PieceType> {
+    match move_type {
+        MoveType::EnPassant(_) => Some(PieceType::Pawn),
+        _ => board.piece_at(mv.destination).map(|piece| piece.piece_type),
+    }
+}
+
+// most valuable victim, least valuable attacker: a pawn taking a queen first, a queen taking a
+// pawn last
+fn mvv_lva(board: &Board, mv: &Move, move_type: MoveType) -> i32 {
+    let victim = captured_piece(board, mv, move_type).map_or(0, value);
+    victim * 10 - value(moved_piece(board, mv)) / 10
+}
+
+// captures only, for the quiescence search
+pub fn order_captures(board: &Board, moves: &mut [(Move, MoveType)]) {
+    moves.sort_by_cached_key(|(mv, move_type)| Reverse(mvv_lva(board, mv, *move_type)));
+}
+
+// static exchange evaluation: the material mv wins once both sides have finished recapturing on
+// its destination, each with their cheapest piece and each free to stop when that suits them
+pub fn see(board: &Board, mv: &Move, move_type: MoveType) -> i32 {
+    let target: usize = mv.destination.into();
+    let mut occupied = board.occupied();
+    if let MoveType::EnPassant(captured) = move_type {
+        occupied &= !(1 << captured);
+    }
+    // gains[d] is what the side making capture d has won, if the other side doesn't recapture
+    // there are at most 32 pieces, so at most 32 captures
+    let mut gains = [0; 32];
+    gains[0] = captured_piece(board, mv, move_type).map_or(0, value);
+    let mut d = 0;
+    let mut from: Bitboard = 1 << usize::from(mv.from);
+    let mut on_target = moved_piece(board, mv);
+    let mut side = board.active_colour();
+    while d + 1 < gains.len() {
+        d += 1;
+        gains[d] = value(on_target) - gains[d - 1];
+        // neither side can come out ahead by going on
+        if (-gains[d - 1]).max(gains[d]) < 0 {
+            break;
+        }
+        occupied &= !from;
+        side = side.opposite();
+        // taking pieces away can uncover sliders behind them
+        let attackers = board.attackers_to(target, occupied) & occupied & board.occupied_by(side);
+        match cheapest_attacker(board, attackers, side) {
+            Some((square, piece_type)) => {
+                from = square;
+                on_target = piece_type;
+            }
+            None => break,
+        }
+    }
+    // the last capture was never made, then each side picks the better of capturing or not
+    while d > 1 {
+        d -= 1;
+        gains[d - 1] = -(-gains[d - 1]).max(gains[d]);
+    }
+    gains[0]
+}
+
+fn cheapest_attacker(
+    board: &Board,
+    attackers: Bitboard,
+    colour: Colour,
+) -> Option<(Bitboard, PieceType)> {
+    EXCHANGE_ORDER.iter().find_map(|piece_type| {
+        let pieces = attackers & board.pieces(colour, *piece_type);
+        (pieces != 0).then(|| (pieces & pieces.wrapping_neg(), *piece_type))
+    })
+}
+
+// what the search has learned about quiet moves: killers are moves that caused a cutoff at the
+// same ply in a sibling position, history counts cutoffs by side and from and to squares
+pub struct MoveOrdering {
+    killers: Vec<[Option<Move>; 2]>,
+    history: Vec<i32>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering {
+            killers: vec![],
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+}
+
+impl MoveOrdering {
+    // forget everything, at the start of a search
+    pub fn clear(&mut self) {
+        self.killers.clear();
+        self.history.fill(0);
+    }
+
+    fn history_index(colour: Colour, mv: &Move) -> usize {
+        (colour as usize * 64 + usize::from(mv.from)) * 64 + usize::from(mv.destination)
+    }
+
+    fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers.get(ply).copied().unwrap_or_default()
+    }
+
+    // sort moves best first
+    pub fn order(
+        &self,
+        board: &Board,
+        moves: &mut [(Move, MoveType)],
+        hash_move: Option<Move>,
+        ply: usize,
+    ) {
+        let killers = self.killers(ply);
+        let colour = board.active_colour();
+        moves.sort_by_cached_key(|(mv, move_type)| {
+            let score = match move_type {
+                _ if Some(*mv) == hash_move => HASH_MOVE,
+                MoveType::PromotionPush | MoveType::PromotionCapture => match mv.promotion {
+                    Some(PieceType::Queen) => PROMOTION + mvv_lva(board, mv, *move_type),
+                    _ => UNDERPROMOTION,
+                },
+                MoveType::Capture | MoveType::EnPassant(_) => {
+                    match see(board, mv, *move_type) >= 0 {
+                        true => GOOD_CAPTURE + mvv_lva(board, mv, *move_type),
+                        false => BAD_CAPTURE + mvv_lva(board, mv, *move_type),
+                    }
+                }
+                _ if Some(*mv) == killers[0] => KILLER,
+                _ if Some(*mv) == killers[1] => KILLER - 1,
+                _ => self.history[Self::history_index(colour, mv)],
+            };
+            Reverse(score)
+        });
+    }
+
+    // mv caused a beta cutoff at ply with depth left to search
+    pub fn record_cutoff(
+        &mut self,
+        board: &Board,
+        mv: Move,
+        move_type: MoveType,
+        depth: usize,
+        ply: usize,
+    ) {
+        if !is_quiet(move_type) {
+            return;
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        // deeper cutoffs saved more work and count for more
+        let i = Self::history_index(board.active_colour(), &mv);
+        self.history[i] += (depth * depth) as i32;
+        if self.history[i] >= HISTORY_LIMIT {
+            self.history.iter_mut().for_each(|h| *h /= 2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new(fen.to_string()).unwrap()
+    }
+
+    fn find(board: &mut Board, uci: &str) -> (Move, MoveType) {
+        board
+            .generate_legal_moves()
+            .into_iter()
+            .find(|(mv, _)| mv.to_string() == uci)
+            .unwrap()
+    }
+
+    fn see_of(fen: &str, uci: &str) -> i32 {
+        let mut board = board(fen);
+        let (mv, move_type) = find(&mut board, uci);
+        see(&board, &mv, move_type)
+    }
+
+    #[test]
+    fn static_exchange() {
+        // an undefended pawn
+        assert_eq!(
+            see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // a defended pawn taken by a knight, which is lost for it
+        assert_eq!(
+            see_of(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5"
+            ),
+            100 - 320
+        );
+        // the queen behind the rook joins in once the rook has gone
+        assert_eq!(see_of("3r3k/8/8/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5"), 100);
+        // en passant
+        assert_eq!(see_of("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 100);
+    }
+
+    #[test]
+    fn captures_before_quiet_moves() {
+        let mut board = board("4k3/8/2p5/1p1q4/2P5/8/8/3QK2N w - - 0 1");
+        let mut moves = board.generate_legal_moves();
+        MoveOrdering::default().order(&board, &mut moves, None, 0);
+        let order: Vec<String> = moves.iter().map(|(mv, _)| mv.to_string()).collect();
+        // pawn takes queen, queen takes queen, then the pawn with the pawn
+        assert_eq!(order[..3], ["c4d5", "d1d5", "c4b5"]);
+        // and the hash move before all of them
+        let (hash_move, _) = find(&mut board, "h1g3");
+        MoveOrdering::default().order(&board, &mut moves, Some(hash_move), 0);
+        assert_eq!(moves[0].0, hash_move);
+    }
+
+    #[test]
+    fn killers_and_history() {
+        let mut board = Board::startpos();
+        let mut ordering = MoveOrdering::default();
+        let (knight, knight_type) = find(&mut board, "g1f3");
+        let (pawn, pawn_type) = find(&mut board, "a2a3");
+        ordering.record_cutoff(&board, knight, knight_type, 3, 2);
+        ordering.record_cutoff(&board, pawn, pawn_type, 1, 2);
+        assert_eq!(ordering.killers(2), [Some(pawn), Some(knight)]);
+        assert_eq!(ordering.killers(1), [None, None]);
+        let mut moves = board.generate_legal_moves();
+        ordering.order(&board, &mut moves, None, 2);
+        assert_eq!((moves[0].0, moves[1].0), (pawn, knight));
+        // at another ply only the history is left, which favours the deeper cutoff
+        ordering.order(&board, &mut moves, None, 5);
+        assert_eq!((moves[0].0, moves[1].0), (knight, pawn));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    board::Board,
+    move_logic::{Move, MoveType},
+};
+
+// generates the legal moves of a position, for make_move()
+type Generator = fn(&mut Board) -> Vec<(Move, MoveType)>;
+
+// positions and node counts from https://www.chessprogramming.org/Perft_Results
+pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+pub const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+pub const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+pub const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+
+// performance test: count the leaf nodes of the legal move tree to a given depth
+// results can be compared against known node counts to find move generation bugs
+impl Board {
+    pub fn perft(&self, depth: usize) -> usize {
+        self.clone()
+            .perft_in_place(depth, Board::generate_legal_moves)
+    }
+
+    // the same count with the old mailbox move generator, to measure the bitboard speedup
+    pub fn perft_mailbox(&self, depth: usize) -> usize {
+        self.clone()
+            .perft_in_place(depth, Board::generate_mailbox_legal_moves)
+    }
+
+    // perft split by root move, useful for narrowing a wrong count down to a single move
+    // at depth 0 there are no root moves, perft(0) only counts the position itself
+    pub fn divide(&self, depth: usize) -> Vec<(Move, usize)> {
+        if depth == 0 {
+            return vec![];
+        }
+        let mut board = self.clone();
+        board
+            .generate_legal_moves()
+            .iter()
+            .map(|(mv, move_type)| {
+                let undo = board.make_move(mv, *move_type);
+                let nodes = board.perft_in_place(depth - 1, Board::generate_legal_moves);
+                board.unmake_move(undo);
+                (*mv, nodes)
+            })
+            .collect()
+    }
+
+    // plays the tree out with make/unmake on a single board
+    fn perft_in_place(&mut self, depth: usize, generate: Generator) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = generate(self);
+        // bulk counting, no need to play out the last ply
+        if depth == 1 {
+            return moves.len();
+        }
+        moves
+            .iter()
+            .map(|(mv, move_type)| {
+                let undo = self.make_move(mv, *move_type);
+                let nodes = self.perft_in_place(depth - 1, generate);
+                self.unmake_move(undo);
+                nodes
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // more positions from the same page
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn check(fen: &str, expected: &[usize]) {
+        let board = Board::new(fen.to_string()).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                board.perft(depth + 1),
+                *nodes,
+                "{} depth {}",
+                fen,
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn perft_startpos() {
+        check(STARTPOS, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        check(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        check(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        check(POSITION_4, &[6, 264, 9467]);
+        check(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        check(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        check(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    // deeper searches take a while in debug builds: cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn perft_deep() {
+        check(STARTPOS, &[20, 400, 8902, 197281, 4865609]);
+        check(KIWIPETE, &[48, 2039, 97862, 4085603]);
+        check(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+        check(POSITION_4, &[6, 264, 9467, 422333]);
+        check(POSITION_5, &[44, 1486, 62379, 2103487]);
+        check(POSITION_6, &[46, 2079, 89890, 3894594]);
+    }
+
+    #[test]
+    fn mailbox_generator_agrees() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let board = Board::new(fen.to_string()).unwrap();
+            assert_eq!(board.perft_mailbox(2), board.perft(2), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let board = Board::new(KIWIPETE.to_string()).unwrap();
+        let divide = board.divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, n)| n).sum::<usize>(), 2039);
+    }
+}
@@ -0,0 +1,976 @@
+use std::{
+    fmt::{self, Display},
+    io::BufRead,
+    str::FromStr,
+};
+
+use crate::{board::Board, errors::BoardError, move_logic::Move, pieces::Colour};
+
+// the seven tag roster, written first and in this order
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+// pgn export lines are kept under this many characters
+const LINE_WIDTH: usize = 80;
+
+// a move in a game's move tree, with everything the pgn said about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    // comments written before the move, only used for the first move of a line
+    pub comments_before: Vec<String>,
+    pub mv: Move,
+    pub san: String,
+    // numeric annotation glyphs, $1 or ! == 1, $2 or ? == 2 ...
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    // alternative lines played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+// a game read from pgn: tags, the position it started from and the move tree
+#[derive(Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<PgnMove>,
+    // "1-0", "0-1", "1/2-1/2" or "*"
+    pub result: String,
+}
+
+impl Game {
+    // a game with no moves yet, for recording a game as it is played
+    pub fn new(start: Board) -> Game {
+        Game {
+            tags: SEVEN_TAG_ROSTER
+                .iter()
+                .map(|name| (name.to_string(), default_tag_value(name).to_string()))
+                .collect(),
+            start,
+            moves: vec![],
+            result: "*".to_string(),
+        }
+    }
+    // add a move to the end of the main line
+    pub fn push_move(&mut self, mv: Move, san: String) {
+        self.moves.push(PgnMove {
+            comments_before: vec![],
+            mv,
+            san,
+            nags: vec![],
+            comments: vec![],
+            variations: vec![],
+        });
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    // the position at the end of the main line, or the error for the first move that can't be
+    // played. Games read from PGN are checked already, moves pushed by hand might not be
+    pub fn final_board(&self) -> Result<Board, BoardError> {
+        let mut board = self.start.clone();
+        for pgn_move in &self.moves {
+            board.process_move(&pgn_move.mv)?;
+        }
+        Ok(board)
+    }
+}
+
+// seven tag roster first, then any other tags, then the starting position if needed
+// followed by the move text wrapped at 80 columns and the result
+impl Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or(default_tag_value(name)),
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape_tag_value(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                writeln!(f, "[{} \"{}\"]", name, escape_tag_value(value))?;
+            }
+        }
+        let fen = self.start.export_fen().map_err(|_| fmt::Error)?;
+        if fen != Board::startpos().export_fen().map_err(|_| fmt::Error)? {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", fen)?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        self.line_tokens(&self.moves, 0, &mut tokens);
+        tokens.push(self.result.clone());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+impl Game {
+    // the move text on one line, without the result
+    pub fn move_text(&self) -> String {
+        let mut tokens = vec![];
+        self.line_tokens(&self.moves, 0, &mut tokens);
+        tokens.join(" ")
+    }
+
+    // move number and whether white is to move, ply plies after the start position
+    fn move_number(&self, ply: usize) -> (usize, bool) {
+        let offset = match self.start.active_colour() {
+            Colour::White => 0,
+            Colour::Black => 1,
+        };
+        (
+            self.start.full_move_number() + (ply + offset) / 2,
+            (ply + offset).is_multiple_of(2),
+        )
+    }
+
+    // move text of a line as space separated tokens, ply is the ply of its first move
+    fn line_tokens(&self, line: &[PgnMove], mut ply: usize, tokens: &mut Vec<String>) {
+        // black's moves only need a number at the start of a line or after an interruption
+        let mut needs_number = true;
+        for pgn_move in line {
+            for comment in &pgn_move.comments_before {
+                comment_tokens(comment, tokens);
+            }
+            let (number, white) = self.move_number(ply);
+            if white {
+                tokens.push(format!("{}.", number));
+            } else if needs_number || !pgn_move.comments_before.is_empty() {
+                tokens.push(format!("{}...", number));
+            }
+            tokens.push(pgn_move.san.clone());
+            needs_number = false;
+            for nag in &pgn_move.nags {
+                tokens.push(format!("${}", nag));
+            }
+            for comment in &pgn_move.comments {
+                comment_tokens(comment, tokens);
+                needs_number = true;
+            }
+            for variation in pgn_move.variations.iter().filter(|v| !v.is_empty()) {
+                let mut variation_tokens = vec![];
+                self.line_tokens(variation, ply, &mut variation_tokens);
+                variation_tokens[0].insert(0, '(');
+                variation_tokens.last_mut().unwrap().push(')');
+                tokens.append(&mut variation_tokens);
+                needs_number = true;
+            }
+            ply += 1;
+        }
+    }
+}
+
+fn default_tag_value(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// a comment split into words so it can be wrapped like the moves
+fn comment_tokens(comment: &str, tokens: &mut Vec<String>) {
+    let mut words: Vec<String> = comment.split_whitespace().map(|w| w.to_string()).collect();
+    if words.is_empty() {
+        words.push(String::new());
+    }
+    words[0].insert(0, '{');
+    words.last_mut().unwrap().push('}');
+    tokens.append(&mut words);
+}
+
+impl FromStr for Game {
+    type Err = BoardError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let game = parser.parse_game()?;
+        // only one game allowed
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(token.error("unexpected text after the game result"));
+        }
+        Ok(game)
+    }
+}
+
+/* ------- D A T A B A S E ---------*/
+
+// a game from a pgn database that couldn't be read
+#[derive(Debug, PartialEq)]
+pub struct PgnDiagnostic {
+    // 1 based position of the game in the file
+    pub game: usize,
+    // line numbers are relative to the start of the file
+    pub error: BoardError,
+}
+
+impl Display for PgnDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "game {}: {}", self.game, self.error)
+    }
+}
+
+// streams games one at a time from a pgn database, only one game's text is held in memory
+// a broken game is returned as a diagnostic and reading carries on with the next one
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    // lines read so far
+    line: usize,
+    // first line of the next game, with its line number
+    pending: Option<(usize, String)>,
+    games: usize,
+    done: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            line: 0,
+            pending: None,
+            games: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnDiagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut text = String::new();
+        let mut first_line = 0;
+        let mut seen_movetext = false;
+        let mut in_comment = false;
+        let mut blank_before = false;
+        loop {
+            let (number, line) = match self.pending.take() {
+                Some(pending) => pending,
+                None => {
+                    let mut line = String::new();
+                    match self.reader.read_line(&mut line) {
+                        Ok(0) => break,
+                        Ok(_) => {}
+                        Err(e) => {
+                            self.done = true;
+                            return Some(Err(PgnDiagnostic {
+                                game: self.games + 1,
+                                error: BoardError::IoError(e.to_string()),
+                            }));
+                        }
+                    }
+                    self.line += 1;
+                    (self.line, line)
+                }
+            };
+            let trimmed = line.trim_start();
+            // a comment left open can't swallow the games after it: a tag pair at the start of a
+            // line after a blank one is taken as the next game's tags
+            let tag_line = match in_comment {
+                true => blank_before && line.starts_with('['),
+                false => trimmed.starts_with('['),
+            };
+            blank_before = trimmed.is_empty();
+            // a tag pair after move text starts the next game
+            if tag_line && seen_movetext {
+                self.pending = Some((number, line));
+                break;
+            }
+            if text.is_empty() {
+                if trimmed.is_empty() {
+                    continue;
+                }
+                first_line = number;
+            }
+            if !tag_line && !in_comment && !trimmed.is_empty() && !trimmed.starts_with('%') {
+                seen_movetext = true;
+            }
+            let comment_before = in_comment;
+            if !tag_line {
+                in_comment = ends_in_comment(&line, in_comment);
+            }
+            text.push_str(&line);
+            // a result token ends the game, even if the next one has no tags
+            if !tag_line && !comment_before && !in_comment && ends_with_result(&line) {
+                break;
+            }
+        }
+        if text.is_empty() {
+            self.done = true;
+            return None;
+        }
+        self.games += 1;
+        Some(Game::from_str(&text).map_err(|error| {
+            let error = match error {
+                BoardError::PgnError {
+                    line,
+                    column,
+                    token,
+                    reason,
+                } => BoardError::PgnError {
+                    line: line + first_line - 1,
+                    column,
+                    token,
+                    reason,
+                },
+                error => error,
+            };
+            PgnDiagnostic {
+                game: self.games,
+                error,
+            }
+        }))
+    }
+}
+
+// whether the last token on a line of move text is a game result
+fn ends_with_result(line: &str) -> bool {
+    let movetext = line.split(';').next().unwrap_or("");
+    matches!(
+        movetext.split_whitespace().last(),
+        Some("1-0" | "0-1" | "1/2-1/2" | "*")
+    )
+}
+
+// whether a {} comment is still open at the end of a line of move text
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            // rest of line comment
+            ';' if !in_comment => break,
+            _ => {}
+        }
+    }
+    in_comment
+}
+
+/* ------- T O K E N S ---------*/
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    // [Name "value"]
+    Tag(String, String),
+    // san moves and move numbers
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Result(String),
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    // 1 based position of the first character
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, reason: &str) -> BoardError {
+        BoardError::PgnError {
+            line: self.line,
+            column: self.column,
+            token: self.text.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+// characters that can make up a move, move number or result
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/".contains(c)
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, BoardError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let (mut i, mut line, mut column) = (0usize, 1usize, 1usize);
+
+    // advance one char, keeping track of line and column
+    let step = |i: &mut usize, line: &mut usize, column: &mut usize| {
+        if chars[*i] == '\n' {
+            *line += 1;
+            *column = 1;
+        } else {
+            *column += 1;
+        }
+        *i += 1;
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start, start_line, start_column) = (i, line, column);
+        let error = |reason: &str, text: String| BoardError::PgnError {
+            line: start_line,
+            column: start_column,
+            token: text,
+            reason: reason.to_string(),
+        };
+        let kind = match c {
+            _ if c.is_whitespace() => {
+                step(&mut i, &mut line, &mut column);
+                continue;
+            }
+            // escape lines are skipped entirely
+            '%' if column == 1 => {
+                while i < chars.len() && chars[i] != '\n' {
+                    step(&mut i, &mut line, &mut column);
+                }
+                continue;
+            }
+            // move number periods carry no information
+            '.' => {
+                step(&mut i, &mut line, &mut column);
+                continue;
+            }
+            '{' => {
+                while i < chars.len() && chars[i] != '}' {
+                    step(&mut i, &mut line, &mut column);
+                }
+                if i == chars.len() {
+                    return Err(error("comment is never closed", "{".to_string()));
+                }
+                step(&mut i, &mut line, &mut column);
+                let text: String = chars[start + 1..i - 1].iter().collect();
+                TokenKind::Comment(text.split_whitespace().collect::<Vec<&str>>().join(" "))
+            }
+            ';' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    step(&mut i, &mut line, &mut column);
+                }
+                let text: String = chars[start + 1..i].iter().collect();
+                TokenKind::Comment(text.trim().to_string())
+            }
+            '(' => {
+                step(&mut i, &mut line, &mut column);
+                TokenKind::OpenVariation
+            }
+            ')' => {
+                step(&mut i, &mut line, &mut column);
+                TokenKind::CloseVariation
+            }
+            '*' => {
+                step(&mut i, &mut line, &mut column);
+                TokenKind::Result("*".to_string())
+            }
+            '$' => {
+                step(&mut i, &mut line, &mut column);
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    step(&mut i, &mut line, &mut column);
+                }
+                let text: String = chars[start..i].iter().collect();
+                match text[1..].parse::<u8>() {
+                    Ok(nag) => TokenKind::Nag(nag),
+                    Err(_) => return Err(error("invalid numeric annotation glyph", text)),
+                }
+            }
+            // move suffix annotations, converted to their nag
+            '!' | '?' => {
+                while i < chars.len() && (chars[i] == '!' || chars[i] == '?') {
+                    step(&mut i, &mut line, &mut column);
+                }
+                let text: String = chars[start..i].iter().collect();
+                let nag = match text.as_str() {
+                    "!" => 1,
+                    "?" => 2,
+                    "!!" => 3,
+                    "??" => 4,
+                    "!?" => 5,
+                    "?!" => 6,
+                    _ => return Err(error("unknown move annotation", text)),
+                };
+                TokenKind::Nag(nag)
+            }
+            '[' => match parse_tag(&chars[i..]) {
+                Ok((name, value, length)) => {
+                    for _ in 0..length {
+                        step(&mut i, &mut line, &mut column);
+                    }
+                    TokenKind::Tag(name, value)
+                }
+                Err(reason) => {
+                    let text: String = chars[i..].iter().take_while(|c| **c != '\n').collect();
+                    return Err(error(reason, text));
+                }
+            },
+            _ if is_symbol_char(c) => {
+                while i < chars.len() && is_symbol_char(chars[i]) {
+                    step(&mut i, &mut line, &mut column);
+                }
+                let text: String = chars[start..i].iter().collect();
+                match text.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" => TokenKind::Result(text),
+                    _ => TokenKind::Symbol(text),
+                }
+            }
+            _ => return Err(error("unexpected character", c.to_string())),
+        };
+        tokens.push(Token {
+            kind,
+            text: chars[start..i].iter().collect(),
+            line: start_line,
+            column: start_column,
+        });
+    }
+    Ok(tokens)
+}
+
+// [Name "value"], where the value may contain \" and \\ escapes
+// returns the name, the value and the number of characters read
+fn parse_tag(chars: &[char]) -> Result<(String, String, usize), &'static str> {
+    let skip_whitespace = |mut i: usize| {
+        while i < chars.len() && chars[i] != '\n' && chars[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = skip_whitespace(1);
+    let mut name = String::new();
+    while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+        name.push(chars[i]);
+        i += 1;
+    }
+    if name.is_empty() {
+        return Err("tag has no name");
+    }
+    i = skip_whitespace(i);
+    if chars.get(i) != Some(&'"') {
+        return Err("tag value must be in double quotes");
+    }
+    i += 1;
+    let mut value = String::new();
+    loop {
+        match chars.get(i) {
+            Some('\\') if i + 1 < chars.len() => {
+                value.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            Some('"') => break,
+            Some('\n') | None => return Err("tag value is never closed"),
+            Some(c) => value.push(*c),
+        }
+        i += 1;
+    }
+    i = skip_whitespace(i + 1);
+    match chars.get(i) {
+        Some(']') => Ok((name, value, i + 1)),
+        _ => Err("tag is never closed"),
+    }
+}
+
+/* ------- P A R S E R ---------*/
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    // tag pairs followed by move text and a result
+    fn parse_game(&mut self) -> Result<Game, BoardError> {
+        let mut tags: Vec<(String, String)> = vec![];
+        while let Some(Token {
+            kind: TokenKind::Tag(name, value),
+            ..
+        }) = self.peek()
+        {
+            tags.push((name.clone(), value.clone()));
+            self.pos += 1;
+        }
+        if tags.is_empty() && self.peek().is_none() {
+            return Err(BoardError::PgnError {
+                line: 1,
+                column: 1,
+                token: "".to_string(),
+                reason: "no game found".to_string(),
+            });
+        }
+
+        // games that don't start from the initial position carry a FEN tag
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::new(fen.clone()).map_err(|e| BoardError::PgnError {
+                line: 1,
+                column: 1,
+                token: fen.clone(),
+                reason: format!("invalid FEN tag: {}", e),
+            })?,
+            None => Board::startpos(),
+        };
+
+        let moves = self.parse_line(&start, false)?;
+        let result = match self.peek() {
+            Some(Token {
+                kind: TokenKind::Result(result),
+                ..
+            }) => result.clone(),
+            Some(token) => return Err(token.error("expected a game result")),
+            None => {
+                return Err(BoardError::PgnError {
+                    line: self.tokens.last().map_or(1, |t| t.line),
+                    column: self.tokens.last().map_or(1, |t| t.column),
+                    token: "".to_string(),
+                    reason: "game has no result (1-0, 0-1, 1/2-1/2 or *)".to_string(),
+                })
+            }
+        };
+        self.pos += 1;
+        Ok(Game {
+            tags,
+            start,
+            moves,
+            result,
+        })
+    }
+
+    // a sequence of moves starting from board, each checked against Board::process_move
+    // variations replay from the position before the move they replace
+    fn parse_line(
+        &mut self,
+        board: &Board,
+        in_variation: bool,
+    ) -> Result<Vec<PgnMove>, BoardError> {
+        let mut line: Vec<PgnMove> = vec![];
+        let mut pending_comments: Vec<String> = vec![];
+        let mut board = board.clone();
+        // position before the last move, where its variations start
+        let mut previous = board.clone();
+
+        while let Some(token) = self.peek().cloned() {
+            match &token.kind {
+                TokenKind::Tag(_, _) => return Err(token.error("tag pair inside move text")),
+                TokenKind::Result(_) => break,
+                TokenKind::CloseVariation => {
+                    if !in_variation {
+                        return Err(token.error("')' without a matching '('"));
+                    }
+                    break;
+                }
+                TokenKind::OpenVariation => {
+                    self.pos += 1;
+                    let variation = self.parse_line(&previous, true)?;
+                    match self.peek() {
+                        Some(Token {
+                            kind: TokenKind::CloseVariation,
+                            ..
+                        }) => self.pos += 1,
+                        _ => return Err(token.error("variation is never closed")),
+                    }
+                    match line.last_mut() {
+                        Some(last) => last.variations.push(variation),
+                        None => return Err(token.error("variation before the first move")),
+                    }
+                    continue;
+                }
+                TokenKind::Comment(comment) => match line.last_mut() {
+                    Some(last) if pending_comments.is_empty() => {
+                        last.comments.push(comment.clone())
+                    }
+                    _ => pending_comments.push(comment.clone()),
+                },
+                TokenKind::Nag(nag) => match line.last_mut() {
+                    Some(last) => last.nags.push(*nag),
+                    None => return Err(token.error("annotation before the first move")),
+                },
+                TokenKind::Symbol(symbol) => {
+                    // move numbers
+                    if symbol.chars().all(|c| c.is_ascii_digit()) {
+                        self.pos += 1;
+                        continue;
+                    }
+                    let mv = board
+                        .parse_san(symbol)
+                        .map_err(|e| token.error(&e.to_string()))?;
+                    let san = board.to_san(&mv);
+                    previous = board.clone();
+                    board
+                        .process_move(&mv)
+                        .map_err(|e| token.error(&e.to_string()))?;
+                    line.push(PgnMove {
+                        comments_before: std::mem::take(&mut pending_comments),
+                        mv,
+                        san,
+                        nags: vec![],
+                        comments: vec![],
+                        variations: vec![],
+                    });
+                }
+            }
+            self.pos += 1;
+        }
+        // comments after the last move of a line
+        if let Some(last) = line.last_mut() {
+            last.comments.append(&mut pending_comments);
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMMORTAL: &str = r#"[Event "London"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Lionel Kieseritzky"]
+[Result "1-0"]
+[ECO "C33"]
+
+1.e4 e5 2.f4 exf4 3.Bc4 Qh4+ 4.Kf1 b5 5.Bxb5 Nf6 6.Nf3 Qh6 7.d3 Nh5 8.Nh4 Qg5
+9.Nf5 c6 10.g4 Nf6 11.Rg1 cxb5 12.h4 Qg6 13.h5 Qg5 14.Qf3 Ng8 15.Bxf4 Qf6
+16.Nc3 Bc5 17.Nd5 Qxb2 18.Bd6 Bxg1 19. e5 Qxa1+ 20. Ke2 Na6 21.Nxg7+ Kd8
+22.Qf6+ Nxf6 23.Be7# 1-0
+"#;
+
+    #[test]
+    fn read_game() {
+        let game = Game::from_str(IMMORTAL).unwrap();
+        assert_eq!(game.tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(game.tag("ECO"), Some("C33"));
+        assert_eq!(game.tags.len(), 8);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.moves.len(), 45);
+        assert_eq!(game.moves[0].san, "e4");
+        assert_eq!(game.moves[44].san, "Be7#");
+        assert_eq!(
+            game.final_board().unwrap().status(),
+            crate::board::GameState::WhiteWin
+        );
+    }
+
+    #[test]
+    fn read_comments_nags_and_variations() {
+        let pgn = r#"[Event "?"]
+{Opening comment} 1. e4 $1 {best by test} e5 (1... c5 2. Nf3 (2. c3) 2... d6 ; Sicilian
+) (1... e6?! {French}) 2. Nf3!? Nc6 {end} *"#;
+        let game = Game::from_str(pgn).unwrap();
+        assert_eq!(game.result, "*");
+        assert_eq!(game.moves.len(), 4);
+        let e4 = &game.moves[0];
+        assert_eq!(e4.comments_before, vec!["Opening comment"]);
+        assert_eq!(e4.nags, vec![1]);
+        assert_eq!(e4.comments, vec!["best by test"]);
+        let e5 = &game.moves[1];
+        assert_eq!(e5.variations.len(), 2);
+        let sicilian = &e5.variations[0];
+        assert_eq!(
+            sicilian
+                .iter()
+                .map(|m| m.san.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["c5", "Nf3", "d6"]
+        );
+        assert_eq!(sicilian[1].variations[0][0].san, "c3");
+        assert_eq!(sicilian[2].comments, vec!["Sicilian"]);
+        assert_eq!(e5.variations[1][0].nags, vec![6]);
+        assert_eq!(e5.variations[1][0].comments, vec!["French"]);
+        assert_eq!(game.moves[2].nags, vec![5]);
+        assert_eq!(game.moves[3].comments, vec!["end"]);
+    }
+
+    #[test]
+    fn read_fen_start_position() {
+        let pgn = r#"[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 30"]
+
+30... Kd7 31. e4 Ke6 1/2-1/2"#;
+        let game = Game::from_str(pgn).unwrap();
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.result, "1/2-1/2");
+        assert_eq!(
+            game.final_board().unwrap().export_fen().unwrap(),
+            "8/8/4k3/8/4P3/8/8/4K3 w - - 1 32"
+        );
+    }
+
+    #[test]
+    fn final_board_of_an_illegal_game() {
+        let mut game = Game::new(Board::startpos());
+        game.push_move(Move::from_str("e2e4").unwrap(), "e4".to_string());
+        game.push_move(Move::from_str("e4e5").unwrap(), "e5".to_string());
+        assert!(game.final_board().is_err());
+    }
+
+    #[test]
+    fn read_escaped_tag_value() {
+        let pgn = r#"[Annotator "A \"quoted\" [name] \\ here"] 1. d4 *"#;
+        let game = Game::from_str(pgn).unwrap();
+        assert_eq!(game.tag("Annotator"), Some(r#"A "quoted" [name] \ here"#));
+    }
+
+    fn error_at(pgn: &str) -> (usize, usize, String) {
+        match Game::from_str(pgn) {
+            Err(BoardError::PgnError {
+                line,
+                column,
+                token,
+                ..
+            }) => (line, column, token),
+            Err(e) => panic!("unexpected error {:?}", e),
+            Ok(_) => panic!("accepted invalid pgn"),
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        // illegal move
+        assert_eq!(error_at("1. e4 e5 2. Ke3 *"), (1, 13, "Ke3".to_string()));
+        // move on the wrong line
+        assert_eq!(
+            error_at("[Event \"?\"]\n\n1. e4 e5\n2. Nf3 Nf3 *"),
+            (4, 8, "Nf3".to_string())
+        );
+        // unbalanced variations
+        assert_eq!(error_at("1. e4 (1. d4 e5 *"), (1, 7, "(".to_string()));
+        assert_eq!(error_at("1. e4 ) e5 *"), (1, 7, ")".to_string()));
+        // no result
+        assert!(matches!(
+            Game::from_str("1. e4 e5"),
+            Err(BoardError::PgnError { .. })
+        ));
+        // unterminated comment and tag
+        assert_eq!(error_at("1. e4 {oops"), (1, 7, "{".to_string()));
+        assert_eq!(error_at("[Event \"?]\n1. e4 *").0, 1);
+        // bad FEN tag
+        assert!(matches!(
+            Game::from_str("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"] *"),
+            Err(BoardError::PgnError { .. })
+        ));
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let game = Game::from_str(IMMORTAL).unwrap();
+        let pgn = game.to_string();
+        assert!(pgn.lines().all(|line| line.len() <= 80));
+        assert!(pgn.starts_with("[Event \"London\"]\n[Site \"London ENG\"]"));
+        assert!(pgn.contains("[ECO \"C33\"]\n\n1. e4 e5 2. f4 exf4 3. Bc4 Qh4+"));
+        assert!(pgn.ends_with("22. Qf6+ Nxf6 23. Be7# 1-0\n"));
+        let again = Game::from_str(&pgn).unwrap();
+        assert_eq!(again.tags, game.tags);
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.to_string(), pgn);
+    }
+
+    #[test]
+    fn write_comments_nags_and_variations() {
+        let pgn = "{Opening comment} 1. e4 $1 {best by test} e5 (1... c5 2. Nf3 (2. c3) 2... d6 ; Sicilian\n) (1... e6?! {French}) 2. Nf3!? Nc6 {end} *";
+        let game = Game::from_str(pgn).unwrap();
+        let movetext = game
+            .to_string()
+            .lines()
+            .skip(8)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        assert_eq!(
+            movetext,
+            "{Opening comment} 1. e4 $1 {best by test} 1... e5 (1... c5 2. Nf3 (2. c3) 2... d6 \
+             {Sicilian}) (1... e6 $6 {French}) 2. Nf3 $5 Nc6 {end} *"
+        );
+        assert_eq!(Game::from_str(&game.to_string()).unwrap().moves, game.moves);
+    }
+
+    #[test]
+    fn write_fen_start_position() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 30";
+        let mut game = Game::new(Board::new(fen.to_string()).unwrap());
+        let mut board = game.start.clone();
+        for san in ["Kd7", "e4", "Ke6"] {
+            let mv = board.parse_san(san).unwrap();
+            game.push_move(mv, board.to_san(&mv));
+            board.process_move(&mv).unwrap();
+        }
+        game.set_tag("White", "Someone \"quoted\"");
+        assert_eq!(
+            game.to_string(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"Someone \\\"quoted\\\"\"]\n[Black \"?\"]\n[Result \"*\"]\n\
+             [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]\n\n30... Kd7 31. e4 Ke6 *\n"
+        );
+        // games from the initial position don't need a FEN tag
+        assert!(!Game::new(Board::startpos()).to_string().contains("FEN"));
+    }
+
+    #[test]
+    fn read_database() {
+        let pgn = format!(
+            "{}\n[Event \"broken\"]\n\n1. e4 e5\n2. Ke3 *\n\n[Event \"third\"]\n{{a comment\n[not a tag] across lines}}\n1. d4 *\n\n1. c4 *\n",
+            IMMORTAL
+        );
+        let games: Vec<Result<Game, PgnDiagnostic>> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].as_ref().unwrap().moves.len(), 45);
+        // errors point into the whole file
+        assert_eq!(
+            games[1].as_ref().err(),
+            Some(&PgnDiagnostic {
+                game: 2,
+                error: BoardError::PgnError {
+                    line: 18,
+                    column: 4,
+                    token: "Ke3".to_string(),
+                    reason: "illegal move: Ke3: no king can move to e3".to_string(),
+                }
+            })
+        );
+        let third = games[2].as_ref().unwrap();
+        assert_eq!(third.tag("Event"), Some("third"));
+        assert_eq!(
+            third.moves[0].comments_before,
+            vec!["a comment [not a tag] across lines"]
+        );
+        // a game without tags right after another one's result
+        assert_eq!(games[3].as_ref().unwrap().moves[0].san, "c4");
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let pgn =
+            "[Event \"open\"]\n\n1. e4 {never closed\n1-0\n\n[Event \"next\"]\n\n1. d4 d5 *\n";
+        let games: Vec<Result<Game, PgnDiagnostic>> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().err().map(|e| e.game), Some(1));
+        let next = games[1].as_ref().unwrap();
+        assert_eq!(next.tag("Event"), Some("next"));
+        assert_eq!(next.move_text(), "1. d4 d5");
+    }
+}
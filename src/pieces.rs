@@ -16,6 +16,12 @@ impl Colour {
             Colour::Black => *self = Colour::White,
         }
     }
+    pub fn opposite(self) -> Colour {
+        match self {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        }
+    }
 }
 
 impl FromStr for Colour {
@@ -114,7 +120,6 @@ impl Display for Piece {
 
 impl Piece {
     pub fn unicode_symbol(&self) -> String {
-        
         if self.colour == Colour::White {
             let p = match &self.piece_type {
                 PieceType::Pawn => "♟",
@@ -0,0 +1,498 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, GameState, RenderStyle},
+    engine::{format_score, Engine, Limits},
+    errors::BoardError,
+    evaluation::evaluate,
+    move_logic::Move,
+    pgn::{Game, PgnMove},
+    pieces::Colour,
+    transposition,
+};
+
+const HELP: &str = "enter moves as e2e4 or e4, or one of the commands:
+  undo    take back the last move
+  redo    play a move taken back again
+  fen     show the position as FEN
+  moves   list the legal moves
+  resign  give up the game
+  draw    claim a draw, or offer one to your opponent
+  flip    turn the board around
+  help    show this message
+  quit    leave the game";
+
+// who makes the moves for a side
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum Player {
+    #[default]
+    Human,
+    Engine,
+}
+
+// time left on each side's clock, indexed by Colour, and what is added after every move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    pub remaining: [Duration; 2],
+    pub increment: Duration,
+}
+
+impl Clock {
+    pub fn new(time: Duration, increment: Duration) -> Clock {
+        Clock {
+            remaining: [time; 2],
+            increment,
+        }
+    }
+}
+
+// "4:05", in minutes and seconds
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// an interactive game between players sharing the terminal, or against the engine
+pub struct Repl {
+    board: Board,
+    // moves taken back, most recent last, in step with the board's redo stack
+    undone: Vec<PgnMove>,
+    // the moves played so far, result kept up to date
+    pub game: Game,
+    // who plays white and black, indexed by Colour
+    pub players: [Player; 2],
+    // how long the engine thinks about each move
+    pub limits: Limits,
+    // size of the engine's transposition table in MB
+    pub hash: usize,
+    // a timed game, the engine then plays to the clock too
+    pub clock: Option<Clock>,
+    // when the side to move started thinking
+    turn_start: Instant,
+    // made for the engine's first move, games between people don't need one
+    engine: Option<Engine>,
+    style: RenderStyle,
+    flipped: bool,
+}
+
+impl Repl {
+    pub fn new(board: Board, style: RenderStyle, flipped: bool) -> Repl {
+        Repl {
+            game: Game::new(board.clone()),
+            board,
+            undone: vec![],
+            players: [Player::Human; 2],
+            limits: Limits::depth(4),
+            hash: transposition::DEFAULT_MEGABYTES,
+            clock: None,
+            turn_start: Instant::now(),
+            engine: None,
+            style,
+            flipped,
+        }
+    }
+
+    // read moves and commands until quit or the end of the input
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "{}", HELP)?;
+        self.show(output)?;
+        let mut lines = input.lines();
+        self.turn_start = Instant::now();
+        loop {
+            self.check_clock(output)?;
+            if self.board.status() == GameState::Active && self.engine_to_move() {
+                self.engine_move(output)?;
+                continue;
+            }
+            let prompt = match (self.board.status(), self.board.active_colour()) {
+                (GameState::Active, Colour::White) => "white to move: ",
+                (GameState::Active, Colour::Black) => "black to move: ",
+                _ => "game over: ",
+            };
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            // thinking about the answer counted too
+            self.check_clock(output)?;
+            if !self.command(line.trim(), &mut lines, output)? {
+                break;
+            }
+            self.game.result = self.board.status().result().to_string();
+        }
+        writeln!(output)
+    }
+
+    // false when the player wants to stop
+    fn command(
+        &mut self,
+        command: &str,
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+        output: &mut impl Write,
+    ) -> io::Result<bool> {
+        match command {
+            "" => {}
+            "quit" | "exit" => return Ok(false),
+            "help" => writeln!(output, "{}", HELP)?,
+            "fen" => match self.board.export_fen() {
+                Ok(fen) => writeln!(output, "{}", fen)?,
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            "moves" => {
+                if self.board.status() != GameState::Active {
+                    writeln!(output, "{}", BoardError::GameOver)?;
+                } else {
+                    let moves: Vec<String> = self
+                        .board
+                        .legal_moves()
+                        .iter()
+                        .map(|mv| self.board.to_san(mv))
+                        .collect();
+                    writeln!(output, "{}", moves.join(" "))?;
+                }
+            }
+            // the clock keeps the time spent on moves that are taken back
+            "undo" => match self.board.undo() {
+                Ok(_) => {
+                    // every move on the board is in the game
+                    self.undone.push(self.game.moves.pop().unwrap());
+                    // take back the engine's reply and the move before it together
+                    while self.engine_to_move() && self.board.undo().is_ok() {
+                        self.undone.push(self.game.moves.pop().unwrap());
+                    }
+                    self.show(output)?;
+                }
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            "redo" => match self.board.redo() {
+                Ok(_) => {
+                    self.game.moves.push(self.undone.pop().unwrap());
+                    while self.engine_to_move() && self.board.redo().is_ok() {
+                        self.game.moves.push(self.undone.pop().unwrap());
+                    }
+                    self.show(output)?;
+                }
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            "flip" => {
+                self.flipped = !self.flipped;
+                self.show(output)?;
+            }
+            "resign" => match self.board.resign() {
+                Ok(()) => writeln!(output, "{}", self.board.status())?,
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            "draw" => match self.board.claim_draw() {
+                Ok(_) => writeln!(output, "{}", self.board.status())?,
+                // nothing to claim, so ask the other player
+                Err(BoardError::NoDrawToClaim) => {
+                    let colour = self.board.active_colour().opposite();
+                    let opponent = match colour {
+                        Colour::White => "white",
+                        Colour::Black => "black",
+                    };
+                    write!(output, "{}, do you accept a draw? (y/n): ", opponent)?;
+                    output.flush()?;
+                    let accepted = match self.players[colour as usize] {
+                        // only when it's doing worse, the score is for the side offering
+                        Player::Engine => {
+                            let accepted = evaluate(&self.board) > 0;
+                            writeln!(output, "{}", if accepted { "y" } else { "n" })?;
+                            accepted
+                        }
+                        Player::Human => match lines.next() {
+                            Some(answer) => answer?.trim().eq_ignore_ascii_case("y"),
+                            None => return Ok(false),
+                        },
+                    };
+                    if accepted {
+                        // the game is active, checked by claim_draw
+                        self.board.agree_draw().unwrap();
+                        writeln!(output, "{}", self.board.status())?;
+                    } else {
+                        writeln!(output, "draw declined")?;
+                    }
+                }
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            _ => {
+                if let Err(e) = self.play(command) {
+                    writeln!(output, "{}", e)?;
+                    return Ok(true);
+                }
+                self.show_move(output)?;
+            }
+        }
+        Ok(true)
+    }
+
+    // a move in coordinate notation or SAN
+    fn play(&mut self, input: &str) -> Result<(), BoardError> {
+        let mv = self.board.parse_move(input)?;
+        self.push_move(mv)
+    }
+
+    fn push_move(&mut self, mv: Move) -> Result<(), BoardError> {
+        let colour = self.board.active_colour() as usize;
+        let san = self.board.to_san(&mv);
+        self.board.process_move(&mv)?;
+        self.undone.clear();
+        self.game.push_move(mv, san);
+        if let Some(clock) = &mut self.clock {
+            let remaining = clock.remaining[colour].saturating_sub(self.turn_start.elapsed());
+            clock.remaining[colour] = remaining + clock.increment;
+        }
+        self.turn_start = Instant::now();
+        Ok(())
+    }
+
+    // end the game if the side to move has run out of time
+    fn check_clock(&mut self, output: &mut impl Write) -> io::Result<()> {
+        let Some(clock) = self.clock else {
+            return Ok(());
+        };
+        let remaining = clock.remaining[self.board.active_colour() as usize];
+        if self.board.status() == GameState::Active && self.turn_start.elapsed() >= remaining {
+            // the game is active
+            self.board.time_out().unwrap();
+            self.game.result = self.board.status().result().to_string();
+            writeln!(output, "{}", self.board.status())?;
+        }
+        Ok(())
+    }
+
+    // the engine's limits, with the time left on the clock when there is one
+    fn engine_limits(&self) -> Limits {
+        let mut limits = self.limits.clone();
+        if let Some(clock) = self.clock {
+            // the engine's own time so far, from the start of its turn
+            let spent = self.turn_start.elapsed();
+            let [white, black] = clock.remaining;
+            let (white, black) = match self.board.active_colour() {
+                Colour::White => (white.saturating_sub(spent), black),
+                Colour::Black => (white, black.saturating_sub(spent)),
+            };
+            limits.wtime = Some(white);
+            limits.btime = Some(black);
+            limits.winc = Some(clock.increment);
+            limits.binc = Some(clock.increment);
+        }
+        limits
+    }
+
+    fn engine_to_move(&self) -> bool {
+        self.players[self.board.active_colour() as usize] == Player::Engine
+    }
+
+    // only called while the game is active, so there is always a move to play
+    fn engine_move(&mut self, output: &mut impl Write) -> io::Result<()> {
+        let limits = self.engine_limits();
+        let hash = self.hash;
+        let engine = self.engine.get_or_insert_with(|| {
+            let mut engine = Engine::new();
+            engine.set_hash_size(hash);
+            engine
+        });
+        let result = engine.search(&self.board, &limits).unwrap();
+        let colour = match self.board.active_colour() {
+            Colour::White => "white",
+            Colour::Black => "black",
+        };
+        let san = self.board.to_san(&result.best_move);
+        self.push_move(result.best_move).unwrap();
+        writeln!(
+            output,
+            "{} plays {} ({})",
+            colour,
+            san,
+            format_score(result.score)
+        )?;
+        self.show_move(output)?;
+        self.game.result = self.board.status().result().to_string();
+        Ok(())
+    }
+
+    // the board after a move, and how the game ended if it did
+    fn show_move(&self, output: &mut impl Write) -> io::Result<()> {
+        self.show(output)?;
+        if self.board.status() != GameState::Active {
+            writeln!(output, "{}", self.board.status())?;
+        }
+        Ok(())
+    }
+
+    fn show(&self, output: &mut impl Write) -> io::Result<()> {
+        write!(output, "{}", self.board.render(self.style, self.flipped))?;
+        if let Some(clock) = self.clock {
+            writeln!(
+                output,
+                "white {}  black {}",
+                format_clock(clock.remaining[0]),
+                format_clock(clock.remaining[1])
+            )?;
+        }
+        if !self.game.moves.is_empty() {
+            writeln!(output, "{}", self.game.move_text())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MAX_DEPTH;
+
+    // play a session, returning it and everything it printed
+    fn session(input: &str) -> (Repl, String) {
+        let mut repl = Repl::new(Board::startpos(), RenderStyle::Unicode, false);
+        let mut output = vec![];
+        repl.run(input.as_bytes(), &mut output).unwrap();
+        (repl, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn play_to_checkmate() {
+        let (repl, output) = session("f2f3\ne5\ng4\nQh4\n");
+        assert!(output.contains("1. f3 e5 2. g4 Qh4#"));
+        assert!(output.contains("checkmate! black wins 0-1"));
+        assert!(output.ends_with("game over: \n"));
+        assert_eq!(repl.game.result, "0-1");
+        // nobody needed the engine
+        assert!(repl.engine.is_none());
+    }
+
+    #[test]
+    fn bad_input_keeps_playing() {
+        let (repl, output) = session("d8d9\ne7e5\nKe2\ne4\n");
+        assert!(output.contains("can't read move: 'd8d9'"));
+        assert!(output.contains("can't move opponents piece!"));
+        assert!(output.contains("illegal move: Ke2"));
+        assert_eq!(repl.game.move_text(), "1. e4");
+        assert_eq!(repl.game.result, "*");
+    }
+
+    #[test]
+    fn commands() {
+        let (repl, output) = session(
+            "e4\ne5\nundo\nundo\nundo\nfen\nredo\nredo\nredo\nundo\nmoves\nNf6\nredo\nquit\nd4\n",
+        );
+        assert!(output.contains("no moves to undo"));
+        assert!(output.contains("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+        assert_eq!(output.matches("no moves to redo").count(), 2);
+        assert!(output.contains("h5 Na6 Nc6 Nf6 Nh6"));
+        // nothing is read after quit
+        assert_eq!(repl.game.move_text(), "1. e4 Nf6");
+
+        let (repl, output) = session("flip\ne4\nresign\nundo\ne5\n");
+        assert!(output.contains("     H     G     F     E     D     C     B     A"));
+        assert!(output.contains("black resigns! white wins 1-0"));
+        // neither the resignation nor the move before it is taken back
+        assert_eq!(output.matches(&BoardError::GameOver.to_string()).count(), 2);
+        assert_eq!(repl.game.result, "1-0");
+    }
+
+    #[test]
+    fn draws() {
+        let (repl, output) = session("e4\ndraw\nn\ndraw\ny\n");
+        assert!(output.contains("white, do you accept a draw? (y/n): draw declined"));
+        assert!(output.contains("draw by agreement! 1/2-1/2"));
+        assert_eq!(repl.game.result, "1/2-1/2");
+
+        // threefold repetition is claimed without asking
+        let (repl, output) = session("Nf3\nNf6\nNg1\nNg8\nNf3\nNf6\nNg1\nNg8\ndraw\n");
+        assert!(output.contains("draw by threefold repetition! 1/2-1/2"));
+        assert!(!output.contains("accept"));
+        assert_eq!(repl.game.result, "1/2-1/2");
+    }
+
+    #[test]
+    fn against_the_engine() {
+        let mut repl = Repl::new(Board::startpos(), RenderStyle::Ascii, false);
+        repl.players = [Player::Human, Player::Engine];
+        repl.limits = Limits::depth(2);
+        repl.hash = 1;
+        let mut output = vec![];
+        repl.run("e4\nd4\nundo\nredo\n".as_bytes(), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("black plays").count(), 2);
+        // undo took back both the engine's reply and the move before it
+        assert_eq!(output.matches("white to move: ").count(), 5);
+        assert_eq!(repl.game.moves.len(), 4);
+        assert!(repl.game.move_text().starts_with("1. e4 "));
+        assert!(repl.engine.is_some());
+    }
+
+    #[test]
+    fn draw_offers_to_the_engine() {
+        // the engine answers for itself, the y meant for it is read as a move
+        let mut repl = Repl::new(Board::startpos(), RenderStyle::Ascii, false);
+        repl.players = [Player::Human, Player::Engine];
+        repl.limits = Limits::depth(1);
+        repl.hash = 1;
+        let mut output = vec![];
+        repl.run("draw\ny\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("black, do you accept a draw? (y/n): n\ndraw declined"));
+        assert_eq!(repl.board.status(), GameState::Active);
+        assert_eq!(repl.game.result, "*");
+
+        // two queens down it takes the draw
+        let board = Board::new("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1".to_string()).unwrap();
+        let mut repl = Repl::new(board, RenderStyle::Ascii, false);
+        repl.players = [Player::Human, Player::Engine];
+        let mut output = vec![];
+        repl.run("draw\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("black, do you accept a draw? (y/n): y"));
+        assert_eq!(repl.game.result, "1/2-1/2");
+    }
+
+    #[test]
+    fn engine_against_itself() {
+        // the engine mates with the extra queen and the game stops at the prompt
+        let board = Board::new("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1".to_string()).unwrap();
+        let mut repl = Repl::new(board, RenderStyle::Ascii, false);
+        repl.players = [Player::Engine; 2];
+        repl.limits = Limits::depth(3);
+        let mut output = vec![];
+        repl.run("".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("checkmate! white wins 1-0"));
+        assert!(output.ends_with("game over: \n"));
+        assert_eq!(repl.game.result, "1-0");
+    }
+
+    #[test]
+    fn timed_games() {
+        let mut repl = Repl::new(Board::startpos(), RenderStyle::Ascii, false);
+        repl.clock = Some(Clock::new(Duration::from_secs(3), Duration::from_secs(1)));
+        repl.players = [Player::Human, Player::Engine];
+        repl.limits = Limits::depth(MAX_DEPTH);
+        let mut output = vec![];
+        repl.run("e4\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("white 0:03  black 0:03"));
+        // both sides get the increment, the engine only spends part of its time
+        let [white, black] = repl.clock.unwrap().remaining;
+        assert!(white > Duration::from_secs(3) && white <= Duration::from_secs(4));
+        assert!(black > Duration::from_secs(2) && black < Duration::from_secs(4));
+        assert!(repl.engine_limits().btime.is_some());
+
+        // a side without time left loses when it next has to move
+        let mut repl = Repl::new(Board::startpos(), RenderStyle::Ascii, false);
+        repl.clock = Some(Clock::new(Duration::ZERO, Duration::ZERO));
+        let mut output = vec![];
+        repl.run("e4\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("white is out of time! black wins 0-1"));
+        assert_eq!(repl.game.result, "0-1");
+        assert!(repl.game.moves.is_empty());
+    }
+}
@@ -0,0 +1,392 @@
+use std::{str::FromStr, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::{
+    board::{Board, GameState},
+    coordinate::Coordinate,
+    errors::BoardError,
+    move_logic::Move,
+    pieces::{Colour, Piece, PieceType},
+};
+
+// piece letter, file and rank qualifiers, capture, destination, promotion
+// eg. Nbxd7 -> (N, b, -, x, d7, -) and e8=Q -> (-, -, -, -, e8, Q)
+fn san_regex() -> &'static Regex {
+    static SAN: OnceLock<Regex> = OnceLock::new();
+    SAN.get_or_init(|| {
+        Regex::new(r"^([KQRBN])?([a-h])?([1-8])?(x)?([a-h][1-8])(?:=?([QRBN]))?$").unwrap()
+    })
+}
+
+fn piece_letter(piece_type: PieceType) -> char {
+    char::from(Piece {
+        piece_type,
+        colour: Colour::White,
+    })
+}
+
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Pawn => "pawn",
+        PieceType::Bishop => "bishop",
+        PieceType::Knight => "knight",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+impl Board {
+    // is mv a king moving two squares?
+    pub(crate) fn is_castle(&self, mv: &Move) -> bool {
+        matches!(self.piece_at(mv.from), Some(p) if p.piece_type == PieceType::King)
+            && (mv.from.file as i8 - mv.destination.file as i8).abs() == 2
+    }
+    // does mv take a piece? (including en passant, where the destination is empty)
+    pub(crate) fn is_capture(&self, mv: &Move) -> bool {
+        self.piece_at(mv.destination).is_some()
+            || matches!(self.piece_at(mv.from), Some(p) if p.piece_type == PieceType::Pawn)
+                && mv.from.file != mv.destination.file
+    }
+
+    // write a legal move in standard algebraic notation with the minimum disambiguation needed
+    // eg. e4, Nbd7, exd6, e8=Q, O-O-O, Qh4e1 and a + or # suffix for check or checkmate
+    // moves that aren't legal are written in coordinate notation instead
+    pub fn to_san(&self, mv: &Move) -> String {
+        let mut next = self.clone();
+        if next.process_move(mv).is_err() {
+            return mv.to_string();
+        }
+        let suffix = match next.status() {
+            GameState::WhiteWin | GameState::BlackWin => "#",
+            _ if next.in_check() => "+",
+            _ => "",
+        };
+        if self.is_castle(mv) {
+            let castle = if mv.destination.file == 'g' {
+                "O-O"
+            } else {
+                "O-O-O"
+            };
+            return format!("{}{}", castle, suffix);
+        }
+
+        let piece_type = self.piece_at(mv.from).unwrap().piece_type;
+        let capture = if self.is_capture(mv) { "x" } else { "" };
+        let promotion = match mv.promotion {
+            Some(piece_type) => format!("={}", piece_letter(piece_type)),
+            None => "".to_string(),
+        };
+        if piece_type == PieceType::Pawn {
+            // pawn captures are always qualified by the file they left
+            let file = if capture.is_empty() {
+                "".to_string()
+            } else {
+                mv.from.file.to_string()
+            };
+            return format!(
+                "{}{}{}{}{}",
+                file, capture, mv.destination, promotion, suffix
+            );
+        }
+
+        // other pieces of the same type that could also move to the destination
+        let rivals: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.destination == mv.destination
+                    && other.from != mv.from
+                    && !self.is_castle(other)
+                    && matches!(self.piece_at(other.from), Some(p) if p.piece_type == piece_type)
+            })
+            .collect();
+        let qualifier = if rivals.is_empty() {
+            "".to_string()
+        } else if rivals.iter().all(|other| other.from.file != mv.from.file) {
+            mv.from.file.to_string()
+        } else if rivals.iter().all(|other| other.from.rank != mv.from.rank) {
+            mv.from.rank.to_string()
+        } else {
+            mv.from.to_string()
+        };
+        format!(
+            "{}{}{}{}{}",
+            piece_letter(piece_type),
+            qualifier,
+            capture,
+            mv.destination,
+            suffix
+        )
+    }
+
+    // a move in coordinate notation (e2e4) or SAN (e4)
+    pub fn parse_move(&self, s: &str) -> Result<Move, BoardError> {
+        match Move::from_str(s) {
+            Ok(mv) => Ok(mv),
+            Err(_) => self.parse_san(s),
+        }
+    }
+
+    // find the legal move written in standard algebraic notation, eg. "Nbxd7+", "e8=Q" or "O-O"
+    pub fn parse_san(&self, san: &str) -> Result<Move, BoardError> {
+        // check, mate and annotation suffixes don't change which move is meant
+        let stripped = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = self.legal_moves();
+
+        // castling, written with the letter O or zero
+        let castle_file = match stripped {
+            "O-O" | "0-0" => Some('g'),
+            "O-O-O" | "0-0-0" => Some('c'),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return legal_moves
+                .into_iter()
+                .find(|mv| self.is_castle(mv) && mv.destination.file == file)
+                .ok_or_else(|| BoardError::IllegalMove(format!("{} is not possible", san)));
+        }
+
+        let caps = san_regex().captures(stripped).ok_or_else(|| {
+            BoardError::SanError(format!("'{}' is not standard algebraic notation", san))
+        })?;
+        let piece_type = match caps.get(1) {
+            Some(m) => Piece::try_from(m.as_str().chars().next().unwrap())?.piece_type,
+            None => PieceType::Pawn,
+        };
+        let from_file = caps.get(2).map(|m| m.as_str().chars().next().unwrap());
+        let from_rank = caps.get(3).map(|m| m.as_str().parse::<usize>().unwrap());
+        let is_capture = caps.get(4).is_some();
+        let destination = Coordinate::from_str(&caps[5])?;
+        let promotion = match caps.get(6) {
+            Some(m) => Some(Piece::try_from(m.as_str().chars().next().unwrap())?.piece_type),
+            None => None,
+        };
+        if promotion.is_some() && piece_type != PieceType::Pawn {
+            return Err(BoardError::SanError(format!(
+                "'{}': only pawns can promote",
+                san
+            )));
+        }
+
+        // every legal move that fits the description, ignoring promotion for now
+        let candidates: Vec<Move> = legal_moves
+            .into_iter()
+            .filter(|mv| {
+                mv.destination == destination
+                    && !self.is_castle(mv)
+                    && matches!(self.piece_at(mv.from), Some(p) if p.piece_type == piece_type)
+                    && from_file.is_none_or(|f| f == mv.from.file)
+                    && from_rank.is_none_or(|r| r == mv.from.rank)
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(BoardError::IllegalMove(format!(
+                "{}: no {} can move to {}",
+                san,
+                piece_name(piece_type),
+                destination
+            )));
+        }
+        let is_promotion = candidates.iter().any(|mv| mv.promotion.is_some());
+        if is_promotion && promotion.is_none() {
+            return Err(BoardError::SanError(format!(
+                "'{}': choose a piece to promote to, eg. {}=Q",
+                san, stripped
+            )));
+        }
+        if !is_promotion && promotion.is_some() {
+            return Err(BoardError::IllegalMove(format!(
+                "{}: a pawn can't promote on {}",
+                san, destination
+            )));
+        }
+
+        let candidates: Vec<Move> = candidates
+            .into_iter()
+            .filter(|mv| mv.promotion == promotion)
+            .collect();
+        if candidates.len() > 1 {
+            let froms: Vec<String> = candidates.iter().map(|mv| mv.from.to_string()).collect();
+            return Err(BoardError::AmbiguousMove(format!(
+                "{}: a {} on any of {} can move to {}",
+                san,
+                piece_name(piece_type),
+                froms.join(", "),
+                destination
+            )));
+        }
+        let mv = candidates[0];
+        if is_capture && !self.is_capture(&mv) {
+            return Err(BoardError::IllegalMove(format!(
+                "{}: there is nothing to capture on {}",
+                san, destination
+            )));
+        }
+        Ok(mv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, san: &str) -> Result<String, BoardError> {
+        let board = Board::new(fen.to_string()).unwrap();
+        board.parse_san(san).map(|mv| mv.to_string())
+    }
+
+    const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn parse_simple_moves() {
+        assert_eq!(san(STARTPOS, "e4"), Ok("e2e4".to_string()));
+        assert_eq!(san(STARTPOS, "e3"), Ok("e2e3".to_string()));
+        assert_eq!(san(STARTPOS, "Nf3"), Ok("g1f3".to_string()));
+        assert_eq!(san(STARTPOS, "Nc3!?"), Ok("b1c3".to_string()));
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+        assert_eq!(san(fen, "exd5"), Ok("e4d5".to_string()));
+        assert_eq!(san(fen, "Bb5+"), Ok("f1b5".to_string()));
+    }
+
+    #[test]
+    fn parse_disambiguation() {
+        // knights on b8 and f6 can both reach d7
+        let fen = "rn2kb1r/pp3ppp/5n2/1B1p4/8/8/PPP2PPP/RNBQK2R b KQkq - 0 1";
+        assert!(matches!(san(fen, "Nd7"), Err(BoardError::AmbiguousMove(_))));
+        assert_eq!(san(fen, "Nbd7"), Ok("b8d7".to_string()));
+        assert_eq!(san(fen, "Nfd7"), Ok("f6d7".to_string()));
+        // rooks on a1 and a5, disambiguated by rank
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w Q - 0 1";
+        assert!(matches!(san(fen, "Ra3"), Err(BoardError::AmbiguousMove(_))));
+        assert_eq!(san(fen, "R1a3"), Ok("a1a3".to_string()));
+        assert_eq!(san(fen, "R5a3"), Ok("a5a3".to_string()));
+        // queens on e4, h4 and h1 need file and rank to pick h4
+        let fen = "8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert!(matches!(
+            san(fen, "Qhe1"),
+            Err(BoardError::AmbiguousMove(_))
+        ));
+        assert_eq!(san(fen, "Qh4e1"), Ok("h4e1".to_string()));
+    }
+
+    #[test]
+    fn parse_capture_and_check_suffix() {
+        let fen = "rn2kb1r/pp1n1ppp/5n2/1B1p4/8/8/PPP2PPP/RNBQK2R w KQkq - 0 1";
+        assert_eq!(san(fen, "Bxd7+"), Ok("b5d7".to_string()));
+        assert_eq!(san(fen, "Bd7+"), Ok("b5d7".to_string()));
+        assert!(matches!(san(fen, "Bxc6"), Err(BoardError::IllegalMove(_))));
+    }
+
+    #[test]
+    fn parse_promotion() {
+        let fen = "3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert!(matches!(san(fen, "e8=Q"), Err(BoardError::IllegalMove(_))));
+        let fen = "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(fen, "e8=Q+"), Ok("e7e8q".to_string()));
+        assert_eq!(san(fen, "e8N"), Ok("e7e8n".to_string()));
+        assert_eq!(san(fen, "exd8=R"), Ok("e7d8r".to_string()));
+        assert!(matches!(san(fen, "e8"), Err(BoardError::SanError(_))));
+        assert!(matches!(san(fen, "Ke2=Q"), Err(BoardError::SanError(_))));
+        assert!(matches!(san(fen, "Kd2=Q"), Err(BoardError::SanError(_))));
+    }
+
+    #[test]
+    fn parse_castling() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "O-O"), Ok("e1g1".to_string()));
+        assert_eq!(san(fen, "0-0-0"), Ok("e1c1".to_string()));
+        // a king move to g1 is not castling
+        assert!(matches!(san(fen, "Kg1"), Err(BoardError::IllegalMove(_))));
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b - - 0 1";
+        assert!(matches!(san(fen, "O-O"), Err(BoardError::IllegalMove(_))));
+    }
+
+    #[test]
+    fn parse_en_passant() {
+        let fen = "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1";
+        assert_eq!(san(fen, "dxe6"), Ok("d5e6".to_string()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            san(STARTPOS, "e5"),
+            Err(BoardError::IllegalMove(_))
+        ));
+        assert!(matches!(
+            san(STARTPOS, "Nf4"),
+            Err(BoardError::IllegalMove(_))
+        ));
+        assert!(matches!(san(STARTPOS, "Zf3"), Err(BoardError::SanError(_))));
+        assert!(matches!(
+            san(STARTPOS, "e2-e4"),
+            Err(BoardError::SanError(_))
+        ));
+        assert!(matches!(san(STARTPOS, ""), Err(BoardError::SanError(_))));
+    }
+
+    fn to_san(fen: &str, mv: &str) -> String {
+        let board = Board::new(fen.to_string()).unwrap();
+        board.to_san(&Move::from_str(mv).unwrap())
+    }
+
+    #[test]
+    fn write_simple_moves() {
+        assert_eq!(to_san(STARTPOS, "e2e4"), "e4");
+        assert_eq!(to_san(STARTPOS, "g1f3"), "Nf3");
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+        assert_eq!(to_san(fen, "e4d5"), "exd5");
+        assert_eq!(to_san(fen, "f1b5"), "Bb5+");
+        let fen = "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1";
+        assert_eq!(to_san(fen, "d5e6"), "dxe6");
+        // not legal
+        assert_eq!(to_san(STARTPOS, "e2e5"), "e2e5");
+    }
+
+    #[test]
+    fn write_disambiguation() {
+        let fen = "rn2kb1r/pp3ppp/5n2/1B1p4/8/8/PPP2PPP/RNBQK2R b KQkq - 0 1";
+        assert_eq!(to_san(fen, "b8d7"), "Nbd7");
+        assert_eq!(to_san(fen, "f6d7"), "Nfd7");
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w Q - 0 1";
+        assert_eq!(to_san(fen, "a1a3"), "R1a3");
+        assert_eq!(to_san(fen, "a5a3"), "R5a3");
+        // only a1 can reach b1
+        assert_eq!(to_san(fen, "a1b1"), "Rb1");
+        let fen = "8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(to_san(fen, "h4e1"), "Qh4e1");
+        assert_eq!(to_san(fen, "e4e1"), "Qee1");
+        assert_eq!(to_san(fen, "h1e1"), "Q1e1");
+    }
+
+    #[test]
+    fn write_promotion_castling_and_mate() {
+        let fen = "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(to_san(fen, "e7e8q"), "e8=Q+");
+        assert_eq!(to_san(fen, "e7d8n"), "exd8=N");
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(to_san(fen, "e1g1"), "O-O");
+        assert_eq!(to_san(fen, "e1c1"), "O-O-O");
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(to_san(fen, "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn san_round_trip() {
+        // every legal move written and read back gives the same move
+        for fen in [
+            STARTPOS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1",
+        ] {
+            let board = Board::new(fen.to_string()).unwrap();
+            for mv in board.legal_moves() {
+                let san = board.to_san(&mv);
+                assert_eq!(board.parse_san(&san), Ok(mv), "{} {}", fen, san);
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{fmt, mem::size_of};
+
+use crate::move_logic::Move;
+
+pub const DEFAULT_MEGABYTES: usize = 16;
+
+// how a stored score relates to the position's true score: searches stop as soon as a move is
+// good enough for a cutoff, or give up on positions where nothing beats alpha
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    // the true score is at least this
+    Lower,
+    // the true score is at most this
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub hash: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    // plies searched below the position
+    pub depth: u8,
+    pub bound: Bound,
+    // the search that stored it, entries from old searches are replaced first
+    generation: u8,
+}
+
+impl Entry {
+    pub fn new(
+        hash: u64,
+        best_move: Option<Move>,
+        score: i32,
+        depth: usize,
+        bound: Bound,
+    ) -> Entry {
+        Entry {
+            hash,
+            best_move,
+            score,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            generation: 0,
+        }
+    }
+}
+
+// counted since the start of the current search
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TableStats {
+    pub probes: u64,
+    // probes that found the position
+    pub hits: u64,
+    // hits that ended the search of the position straight away
+    pub cutoffs: u64,
+    pub stores: u64,
+    // stores that pushed out another position
+    pub overwrites: u64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.probes {
+            0 => 0.0,
+            probes => self.hits as f64 / probes as f64,
+        }
+    }
+}
+
+impl fmt::Display for TableStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} probes, {:.1}% hits, {} cutoffs, {} stores, {} overwrites",
+            self.probes,
+            self.hit_rate() * 100.0,
+            self.cutoffs,
+            self.stores,
+            self.overwrites
+        )
+    }
+}
+
+// search results by position hash, one entry per slot. A position seen again through another move
+// order, or in the next iteration, can reuse them instead of being searched again
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+    stats: TableStats,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_MEGABYTES)
+    }
+}
+
+impl TranspositionTable {
+    // as many entries as fit in the given size, at least one
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let len = (megabytes * 1024 * 1024 / size_of::<Option<Entry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; len],
+            generation: 0,
+            stats: TableStats::default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.stats = TableStats::default();
+    }
+
+    // called at the start of every search, ageing what's already stored
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.stats = TableStats::default();
+    }
+
+    // spreads hashes evenly over any table length
+    fn index(&self, hash: u64) -> usize {
+        ((hash as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    pub fn probe(&mut self, hash: u64) -> Option<Entry> {
+        self.stats.probes += 1;
+        let entry = self.peek(hash)?;
+        self.stats.hits += 1;
+        Some(entry)
+    }
+
+    // probe without counting it, for looking at results rather than searching
+    pub fn peek(&self, hash: u64) -> Option<Entry> {
+        self.entries[self.index(hash)].filter(|entry| entry.hash == hash)
+    }
+
+    // the search used a probed entry's score instead of searching on
+    pub fn record_cutoff(&mut self) {
+        self.stats.cutoffs += 1;
+    }
+
+    // replace by depth: a deeper search of another position is kept unless it is left over
+    // from an earlier search
+    pub fn store(&mut self, mut entry: Entry) {
+        entry.generation = self.generation;
+        let i = self.index(entry.hash);
+        if let Some(old) = self.entries[i] {
+            if old.hash != entry.hash {
+                if old.generation == self.generation && old.depth > entry.depth {
+                    return;
+                }
+                self.stats.overwrites += 1;
+            } else if entry.best_move.is_none() {
+                // a shallower look at the same position still knows its best move
+                entry.best_move = old.best_move;
+            }
+        }
+        self.stats.stores += 1;
+        self.entries[i] = Some(entry);
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    // permille of the table used by the current search, sampled from its start
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let used = sample
+            .iter()
+            .filter(|entry| matches!(entry, Some(entry) if entry.generation == self.generation))
+            .count();
+        used * 1000 / sample.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: u64, depth: usize) -> Entry {
+        Entry::new(hash, None, 10, depth, Bound::Exact)
+    }
+
+    #[test]
+    fn sized_in_megabytes() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(
+            table.entries.len(),
+            1024 * 1024 / size_of::<Option<Entry>>()
+        );
+        assert_eq!(TranspositionTable::new(0).entries.len(), 1);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        let mv = "e2e4".parse::<Move>().unwrap();
+        table.store(Entry::new(42, Some(mv), -30, 5, Bound::Lower));
+        let found = table.probe(42).unwrap();
+        assert_eq!(
+            (found.best_move, found.score, found.depth, found.bound),
+            (Some(mv), -30, 5, Bound::Lower)
+        );
+        assert_eq!(table.probe(43), None);
+        let stats = table.stats();
+        assert_eq!((stats.probes, stats.hits, stats.stores), (2, 1, 1));
+        assert_eq!(stats.hit_rate(), 0.5);
+        table.clear();
+        assert_eq!(table.probe(42), None);
+    }
+
+    #[test]
+    fn replace_by_depth() {
+        // every position shares the only slot
+        let mut table = TranspositionTable::new(0);
+        table.store(entry(1, 6));
+        table.store(entry(2, 3));
+        assert_eq!(table.probe(1).unwrap().depth, 6);
+        table.store(entry(2, 6));
+        assert_eq!(table.probe(2).unwrap().depth, 6);
+        // the same position is always updated, keeping its best move
+        let mv = "g1f3".parse::<Move>().unwrap();
+        table.store(Entry::new(2, Some(mv), 0, 7, Bound::Exact));
+        table.store(entry(2, 1));
+        assert_eq!(table.probe(2).unwrap().best_move, Some(mv));
+        // entries from an old search make way
+        table.store(entry(3, 0));
+        assert_eq!(table.probe(3), None);
+        table.new_search();
+        table.store(entry(3, 0));
+        assert_eq!(table.probe(3).unwrap().depth, 0);
+        assert_eq!(table.stats().overwrites, 1);
+    }
+
+    #[test]
+    fn hashfull_counts_this_search() {
+        let mut table = TranspositionTable::new(1);
+        assert_eq!(table.hashfull(), 0);
+        for hash in 0..table.entries.len() as u64 {
+            table.store(entry(hash.wrapping_mul(0x9E37_79B9_7F4A_7C15), 1));
+        }
+        assert!(table.hashfull() > 500);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+    }
+}
@@ -0,0 +1,532 @@
+use std::{
+    io::{BufRead, Write},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    board::Board,
+    engine::{mate_in, Engine, Limits, SearchResult, MAX_DEPTH, MAX_SKILL_LEVEL},
+    errors::BoardError,
+    move_logic::Move,
+    transposition::DEFAULT_MEGABYTES,
+};
+
+// the universal chess interface: a GUI sends commands one per line and the engine answers on its
+// output. Searches run on their own thread, so stop and isready are answered while it thinks
+
+const MAX_HASH: usize = 4096;
+const MAX_MULTI_PV: usize = 64;
+
+pub struct Uci<W: Write + Send + 'static> {
+    board: Board,
+    // here while idle, lent to the search thread while it searches
+    engine: Option<Engine>,
+    search: Option<JoinHandle<Engine>>,
+    stop: Arc<AtomicBool>,
+    // the search under way only gives its move once stopped
+    infinite: bool,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(output: W) -> Uci<W> {
+        let engine = Engine::new();
+        Uci {
+            board: Board::startpos(),
+            stop: engine.stop_flag(),
+            engine: Some(engine),
+            search: None,
+            infinite: false,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+
+    // answer commands until quit or the end of the input, where a search with limits is left
+    // to finish
+    pub fn run(&mut self, input: impl BufRead) -> Result<(), BoardError> {
+        for line in input.lines() {
+            let line = line.map_err(|e| BoardError::IoError(e.to_string()))?;
+            if !self.command(&line)? {
+                self.stop_search();
+                return Ok(());
+            }
+        }
+        match self.infinite {
+            true => self.stop_search(),
+            false => self.wait(),
+        }
+        Ok(())
+    }
+
+    // false after quit. Commands that can't be carried out are reported to the GUI, only
+    // failing to write to it is an error
+    fn command(&mut self, line: &str) -> Result<bool, BoardError> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = tokens.split_first() else {
+            return Ok(true);
+        };
+        let result = match *command {
+            "uci" => self.identify(),
+            "isready" => send(&self.output, "readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.engine().clear();
+                self.board = Board::startpos();
+                Ok(())
+            }
+            "position" => {
+                self.stop_search();
+                parse_position(args).map(|board| self.board = board)
+            }
+            "go" => {
+                self.stop_search();
+                self.go(args)
+            }
+            "stop" => {
+                self.stop_search();
+                Ok(())
+            }
+            "setoption" => {
+                self.stop_search();
+                self.set_option(args)
+            }
+            "quit" => return Ok(false),
+            // nothing to do for these
+            "debug" | "register" => Ok(()),
+            "ponderhit" => Err(BoardError::UciError(
+                "pondering isn't supported".to_string(),
+            )),
+            _ => Err(BoardError::UciError(format!(
+                "unknown command '{}'",
+                command
+            ))),
+        };
+        match result {
+            Err(BoardError::IoError(e)) => Err(BoardError::IoError(e)),
+            Err(e) => send(&self.output, &format!("info string {}", e)).map(|_| true),
+            Ok(()) => Ok(true),
+        }
+    }
+
+    // only called while no search is running, which has the engine
+    fn engine(&mut self) -> &mut Engine {
+        self.engine.as_mut().unwrap()
+    }
+
+    fn identify(&self) -> Result<(), BoardError> {
+        let lines = [
+            format!("id name chess-cli {}", env!("CARGO_PKG_VERSION")),
+            "id author the chess-cli authors".to_string(),
+            format!(
+                "option name Hash type spin default {} min 1 max {}",
+                DEFAULT_MEGABYTES, MAX_HASH
+            ),
+            // the search runs on one thread
+            "option name Threads type spin default 1 min 1 max 1".to_string(),
+            format!(
+                "option name MultiPV type spin default 1 min 1 max {}",
+                MAX_MULTI_PV
+            ),
+            format!(
+                "option name Skill Level type spin default {0} min 0 max {0}",
+                MAX_SKILL_LEVEL
+            ),
+            "uciok".to_string(),
+        ];
+        lines.iter().try_for_each(|line| send(&self.output, line))
+    }
+
+    fn set_option(&mut self, args: &[&str]) -> Result<(), BoardError> {
+        // names and values can both have spaces in them
+        let (name, value) = match args {
+            ["name", rest @ ..] => match rest.iter().position(|token| *token == "value") {
+                Some(i) => (rest[..i].join(" "), rest[i + 1..].join(" ")),
+                None => (rest.join(" "), String::new()),
+            },
+            _ => return Err(BoardError::UciError("setoption needs a name".to_string())),
+        };
+        match name.to_lowercase().as_str() {
+            "hash" => {
+                let megabytes = spin(&name, &value, 1, MAX_HASH)?;
+                self.engine().set_hash_size(megabytes);
+            }
+            "threads" => {
+                spin(&name, &value, 1, 1)?;
+            }
+            "multipv" => {
+                let lines = spin(&name, &value, 1, MAX_MULTI_PV)?;
+                self.engine().set_multi_pv(lines);
+            }
+            "skill level" => {
+                let level = spin(&name, &value, 0, MAX_SKILL_LEVEL)?;
+                self.engine().set_skill_level(level);
+            }
+            _ => return Err(BoardError::UciError(format!("unknown option '{}'", name))),
+        }
+        Ok(())
+    }
+
+    // start searching the current position, reporting each iteration as it completes and the
+    // best move at the end
+    fn go(&mut self, args: &[&str]) -> Result<(), BoardError> {
+        let (limits, infinite) = parse_go(args)?;
+        let legal = self.board.legal_moves();
+        if let Some(mv) = limits.searchmoves.iter().find(|mv| !legal.contains(mv)) {
+            return Err(BoardError::UciError(format!(
+                "searchmoves {} isn't legal",
+                mv
+            )));
+        }
+        let mut engine = self.engine.take().unwrap();
+        let board = self.board.clone();
+        let output = self.output.clone();
+        let stop = self.stop.clone();
+        // no search is running, so no stop can be meant for this one yet
+        stop.store(false, Ordering::Relaxed);
+        self.infinite = infinite;
+        self.search = Some(thread::spawn(move || {
+            // the GUI has gone if its end can't be written to, nothing is left to tell it
+            let result = engine.search_reporting(&board, &limits, |result| {
+                let _ = send(&output, &info(result));
+            });
+            // even with nothing left to search
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let best = match result {
+                Ok(result) => format!("bestmove {}", result.best_move),
+                // checkmate or stalemate, there is no move to give
+                Err(_) => "bestmove 0000".to_string(),
+            };
+            let _ = send(&output, &best);
+            engine
+        }));
+        Ok(())
+    }
+
+    // end the search under way, which still gives its best move
+    fn stop_search(&mut self) {
+        if self.search.is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.wait();
+        }
+    }
+
+    // let the search under way finish and take the engine back
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.engine = Some(search.join().unwrap());
+        }
+    }
+}
+
+// write a line and send it on straight away, the GUI is waiting for it
+fn send<W: Write>(output: &Mutex<W>, line: &str) -> Result<(), BoardError> {
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", line)
+        .and_then(|_| output.flush())
+        .map_err(|e| BoardError::IoError(e.to_string()))
+}
+
+fn info(result: &SearchResult) -> String {
+    let score = match mate_in(result.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let millis = result.time.as_millis() as u64;
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+    format!(
+        "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        result.multipv,
+        score,
+        result.nodes,
+        result.nodes * 1000 / millis.max(1),
+        millis,
+        pv.join(" ")
+    )
+}
+
+fn spin(name: &str, value: &str, min: usize, max: usize) -> Result<usize, BoardError> {
+    match value.parse::<usize>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(BoardError::UciError(format!(
+            "{} must be a number from {} to {}",
+            name, min, max
+        ))),
+    }
+}
+
+// "startpos" or "fen" and the six fields of a FEN, then optionally "moves" and the moves played
+// from there in coordinate notation
+fn parse_position(args: &[&str]) -> Result<Board, BoardError> {
+    let (mut board, rest) = match args {
+        ["startpos", rest @ ..] => (Board::startpos(), rest),
+        ["fen", rest @ ..] => {
+            let end = rest
+                .iter()
+                .position(|token| *token == "moves")
+                .unwrap_or(rest.len());
+            (Board::new(rest[..end].join(" "))?, &rest[end..])
+        }
+        _ => {
+            return Err(BoardError::UciError(
+                "position needs startpos or fen".to_string(),
+            ))
+        }
+    };
+    match rest {
+        [] => {}
+        ["moves", moves @ ..] => {
+            for token in moves {
+                let mv = Move::from_str(token)?;
+                board
+                    .process_move(&mv)
+                    .map_err(|_| BoardError::IllegalMove(token.to_string()))?;
+            }
+        }
+        _ => {
+            return Err(BoardError::UciError(format!(
+                "expected moves, found '{}'",
+                rest[0]
+            )))
+        }
+    }
+    Ok(board)
+}
+
+// the limits of a go command, and whether it is infinite
+fn parse_go(args: &[&str]) -> Result<(Limits, bool), BoardError> {
+    let mut limits = Limits::default();
+    let mut infinite = false;
+    let mut tokens = args.iter().peekable();
+    while let Some(token) = tokens.next() {
+        let mut number = || -> Result<i64, BoardError> {
+            tokens
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| BoardError::UciError(format!("go {} needs a number", token)))
+        };
+        // clocks can run below zero in some GUIs
+        let millis = |value: i64| Duration::from_millis(value.max(0) as u64);
+        match *token {
+            "depth" => limits.depth = Some(number()?.max(1) as usize),
+            "nodes" => limits.nodes = Some(number()?.max(1) as u64),
+            "movetime" => limits.movetime = Some(millis(number()?)),
+            "wtime" => limits.wtime = Some(millis(number()?)),
+            "btime" => limits.btime = Some(millis(number()?)),
+            "winc" => limits.winc = Some(millis(number()?)),
+            "binc" => limits.binc = Some(millis(number()?)),
+            "movestogo" => limits.movestogo = Some(number()?.max(1) as u32),
+            // a mate in n moves takes 2n - 1 plies to find
+            "mate" => {
+                let moves = number()?.clamp(1, MAX_DEPTH as i64) as usize;
+                let plies = (moves * 2 - 1).min(MAX_DEPTH);
+                limits.depth = Some(limits.depth.map_or(plies, |depth| depth.min(plies)));
+            }
+            "infinite" => infinite = true,
+            // every move up to the next keyword
+            "searchmoves" => {
+                while let Some(mv) = tokens.peek().and_then(|mv| Move::from_str(mv).ok()) {
+                    limits.searchmoves.push(mv);
+                    tokens.next();
+                }
+            }
+            // no Ponder option is offered, so a GUI has no reason to ask
+            "ponder" => {
+                return Err(BoardError::UciError(
+                    "pondering isn't supported".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok((limits, infinite))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // everything written so far
+    fn output(uci: &Uci<Vec<u8>>) -> String {
+        String::from_utf8(uci.output.lock().unwrap().clone()).unwrap()
+    }
+
+    fn session(input: &str) -> (Uci<Vec<u8>>, String) {
+        let mut uci = Uci::new(vec![]);
+        uci.run(input.as_bytes()).unwrap();
+        let output = output(&uci);
+        (uci, output)
+    }
+
+    #[test]
+    fn handshake() {
+        let (_, output) = session("uci\nisready\n");
+        assert!(output.starts_with("id name chess-cli"));
+        assert!(output.contains("option name Hash type spin default 16 min 1 max 4096\n"));
+        assert!(output.contains("option name Skill Level type spin default 20 min 0 max 20\n"));
+        assert!(output.ends_with("uciok\nreadyok\n"));
+    }
+
+    #[test]
+    fn positions() {
+        let (uci, _) = session("position startpos moves e2e4 e7e5 g1f3\n");
+        assert_eq!(
+            uci.board.export_fen().unwrap(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        // castling and promotion
+        let (uci, _) =
+            session("position fen 4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1 moves e1c1 e8f7 b7b8q\n");
+        assert_eq!(
+            uci.board.export_fen().unwrap(),
+            "1Q6/5k2/8/8/8/8/8/2KR4 b - - 0 2"
+        );
+        // the game so far counts towards repetitions
+        let (uci, _) = session("position startpos moves g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1\n");
+        assert_eq!(uci.board.position_hashes().len(), 8);
+    }
+
+    #[test]
+    fn search_and_report() {
+        let (_, output) = session("position startpos moves e2e4\ngo depth 3\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("info depth 1 multipv 1 score cp "));
+        assert!(lines[2].starts_with("info depth 3 "));
+        assert!(lines[2].contains(" pv "));
+        assert!(lines[3].starts_with("bestmove "));
+
+        // mates are given in moves
+        let (_, output) =
+            session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo wtime 1000 btime 1000\n");
+        assert!(output.contains("score mate 1 "));
+        assert!(output.ends_with("bestmove a1a8\n"));
+        // and with no move to play there is no best move
+        let (_, output) =
+            session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1 moves a1a8\ngo depth 2\n");
+        assert_eq!(output, "bestmove 0000\n");
+        // but a game drawn by rule still has moves
+        let (uci, output) = session("position fen 8/8/4k3/8/8/3NK3/8/8 w - - 0 1\ngo depth 3\n");
+        let best = output.lines().last().unwrap().split(' ').nth(1).unwrap();
+        assert!(
+            uci.board.legal_moves().contains(&best.parse().unwrap()),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn search_some_moves() {
+        // Ra8# is left out, so black is given time to defend
+        let (_, output) = session(
+            "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2 searchmoves a1b1 a1a2\n",
+        );
+        assert!(!output.contains("score mate"));
+        assert!(output.ends_with("bestmove a1b1\n") || output.ends_with("bestmove a1a2\n"));
+        // and no ponder move is suggested, there is no pondering
+        assert!(!output.contains("ponder"));
+        let (_, output) =
+            session("position startpos\ngo depth 2 searchmoves e2e4 e2e5\nponderhit\n");
+        assert_eq!(
+            output,
+            "info string uci error: searchmoves e2e5 isn't legal\n\
+             info string uci error: pondering isn't supported\n"
+        );
+    }
+
+    #[test]
+    fn stop_an_infinite_search() {
+        let mut uci = Uci::new(vec![]);
+        uci.command("go infinite").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(uci.search.is_some());
+        assert!(!output(&uci).contains("bestmove"));
+        // still ready to take commands
+        uci.command("isready").unwrap();
+        assert!(output(&uci).contains("readyok"));
+        uci.command("stop").unwrap();
+        assert!(uci.search.is_none());
+        let output = output(&uci);
+        assert_eq!(output.matches("bestmove").count(), 1);
+        assert!(output.contains("info depth 1 "));
+    }
+
+    #[test]
+    fn options() {
+        let (_, output) = session(
+            "setoption name MultiPV value 3\nsetoption name Hash value 1\nposition startpos\ngo depth 2\n",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        // three lines for each depth, best first
+        assert_eq!(lines.len(), 7);
+        assert!(lines[3].starts_with("info depth 2 multipv 1 "));
+        assert!(lines[5].starts_with("info depth 2 multipv 3 "));
+
+        let (_, output) = session(
+            "setoption name Skill Level value 30\nsetoption name Threads value 4\nsetoption name Colour value red\n",
+        );
+        assert_eq!(
+            output,
+            "info string uci error: Skill Level must be a number from 0 to 20\n\
+             info string uci error: Threads must be a number from 1 to 1\n\
+             info string uci error: unknown option 'Colour'\n"
+        );
+        // a weak engine only looks one ply ahead
+        let (_, output) = session("setoption name Skill Level value 0\ngo depth 5\n");
+        assert!(!output.contains("info depth 2 "));
+        assert!(output.contains("bestmove "));
+    }
+
+    #[test]
+    fn go_limits() {
+        let (limits, infinite) = parse_go(&[
+            "wtime",
+            "-20",
+            "btime",
+            "5000",
+            "movestogo",
+            "10",
+            "infinite",
+        ])
+        .unwrap();
+        assert_eq!(limits.wtime, Some(Duration::ZERO));
+        assert_eq!(limits.btime, Some(Duration::from_secs(5)));
+        assert_eq!(limits.movestogo, Some(10));
+        assert!(infinite);
+        let (limits, _) = parse_go(&["depth", "8", "mate", "3"]).unwrap();
+        assert_eq!(limits.depth, Some(5));
+        // however many moves are asked for, the search is no deeper than it can go
+        let (limits, _) = parse_go(&["mate", &i64::MAX.to_string()]).unwrap();
+        assert_eq!(limits.depth, Some(MAX_DEPTH));
+        let (limits, _) = parse_go(&["searchmoves", "e2e4", "g1f3", "depth", "4"]).unwrap();
+        let moves: Vec<String> = limits.searchmoves.iter().map(Move::to_string).collect();
+        assert_eq!(moves, ["e2e4", "g1f3"]);
+        assert_eq!(limits.depth, Some(4));
+        assert!(parse_go(&["ponder", "wtime", "1000"]).is_err());
+    }
+
+    #[test]
+    fn bad_commands() {
+        let (uci, output) = session(
+            "hello\nposition fen 8/8/8 w - - 0 1\nposition startpos moves e2e5\ngo depth x\n",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "info string uci error: unknown command 'hello'");
+        assert!(lines[1].starts_with("info string invalid fen"));
+        assert_eq!(lines[2], "info string illegal move: e2e5");
+        assert_eq!(lines[3], "info string uci error: go depth needs a number");
+        assert_eq!(lines.len(), 4);
+        // the position is left as it was
+        assert_eq!(uci.board.export_fen(), Board::startpos().export_fen());
+        // and nothing is read after quit
+        let (_, output) = session("quit\nisready\n");
+        assert_eq!(output, "");
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{board::CastlingRights, pieces::Piece};
+
+// zobrist hashing: a position's key is the xor of a random number for each of its features,
+// so a move only has to xor out what it changes and xor in what it adds
+
+// one key per piece on each square, then black to move, the four castling rights and the
+// eight en passant files
+const KEY_COUNT: usize = 2 * 6 * 64 + 1 + 4 + 8;
+const BLACK_TO_MOVE: usize = 2 * 6 * 64;
+const CASTLING: usize = BLACK_TO_MOVE + 1;
+const EN_PASSANT: usize = CASTLING + 4;
+
+// generated at compile time from a fixed seed, so keys are the same in every build and run
+static KEYS: [u64; KEY_COUNT] = generate_keys(0x9E37_79B9_7F4A_7C15);
+
+// xorshift64*
+const fn generate_keys(seed: u64) -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state = seed;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        keys[i] = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        i += 1;
+    }
+    keys
+}
+
+pub fn piece(piece: Piece, square: usize) -> u64 {
+    KEYS[((piece.colour as usize * 6) + piece.piece_type as usize) * 64 + square]
+}
+
+pub fn black_to_move() -> u64 {
+    KEYS[BLACK_TO_MOVE]
+}
+
+pub fn castling(rights: CastlingRights) -> u64 {
+    [rights.k_w, rights.q_w, rights.k_b, rights.q_b]
+        .iter()
+        .enumerate()
+        .filter(|(_, right)| **right)
+        .fold(0, |key, (i, _)| key ^ KEYS[CASTLING + i])
+}
+
+// file 0 == a
+pub fn en_passant(file: usize) -> u64 {
+    KEYS[EN_PASSANT + file]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys = KEYS.to_vec();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), KEY_COUNT);
+        assert!(!keys.contains(&0));
+    }
+}